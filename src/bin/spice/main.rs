@@ -0,0 +1,200 @@
+//! `spice`, a command-line client for Spice.ai.
+//!
+//! Built with the `cli` feature: `cargo install spiceai --features cli`.
+
+mod output;
+mod repl;
+
+use std::error::Error;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use chrono::{DateTime, Utc};
+use clap::{Args, Parser, Subcommand};
+use spiceai::{Client, Granularity, SpiceClientBuilder, TradingPair};
+
+use output::OutputFormat;
+
+type CliResult<T> = std::result::Result<T, Box<dyn Error>>;
+
+/// Query Spice.ai from the command line.
+///
+/// The API key is read from `--api-key`, the `API_KEY` environment variable
+/// or a `.env.local` file in the working directory.
+#[derive(Parser)]
+#[command(name = "spice", version)]
+struct Cli {
+    #[command(flatten)]
+    connection: ConnectionArgs,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Args)]
+struct ConnectionArgs {
+    /// Spice.ai API key.
+    #[arg(long, env = "API_KEY", hide_env_values = true, global = true)]
+    api_key: Option<String>,
+
+    /// Connect to a local Spice runtime instead of the Spice.ai cloud.
+    #[arg(long, global = true)]
+    local: bool,
+
+    /// Overrides the Flight endpoint.
+    #[arg(long, global = true)]
+    flight_url: Option<String>,
+
+    /// Overrides the Firecache endpoint.
+    #[arg(long, global = true)]
+    firecache_url: Option<String>,
+
+    /// Overrides the HTTP API endpoint.
+    #[arg(long, global = true)]
+    http_url: Option<String>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Runs a SQL query against the Flight endpoint.
+    Query(QueryArgs),
+    /// Runs a SQL query against the Firecache endpoint.
+    FireQuery(QueryArgs),
+    /// Starts an interactive SQL shell.
+    #[command(alias = "shell")]
+    Repl,
+    /// Lists the supported trading pairs.
+    Pairs,
+    /// Shows the latest prices of trading pairs.
+    Prices {
+        /// Trading pairs, e.g. `BTC-USD`.
+        #[arg(required = true)]
+        pairs: Vec<TradingPair>,
+    },
+    /// Shows historical prices of trading pairs.
+    Historical {
+        /// Trading pairs, e.g. `BTC-USD`.
+        #[arg(required = true)]
+        pairs: Vec<TradingPair>,
+
+        /// Start of the range, as an RFC 3339 timestamp.
+        #[arg(long)]
+        start: Option<DateTime<Utc>>,
+
+        /// End of the range, as an RFC 3339 timestamp.
+        #[arg(long)]
+        end: Option<DateTime<Utc>>,
+
+        /// Interval between prices: 1m, 5m, 15m, 1h, 4h or 1d.
+        #[arg(long)]
+        granularity: Option<Granularity>,
+    },
+}
+
+#[derive(Args)]
+struct QueryArgs {
+    /// The SQL query, read from standard input when omitted or `-`.
+    sql: Option<String>,
+
+    /// Reads the SQL query from a file.
+    #[arg(short, long, conflicts_with = "sql")]
+    file: Option<PathBuf>,
+
+    /// How results are printed.
+    #[arg(short = 'o', long, value_enum, default_value_t = OutputFormat::Table)]
+    format: OutputFormat,
+}
+
+impl QueryArgs {
+    fn read_sql(&self) -> CliResult<String> {
+        let sql = match (&self.sql, &self.file) {
+            (_, Some(path)) => std::fs::read_to_string(path)?,
+            (Some(sql), None) if sql != "-" => sql.clone(),
+            _ => {
+                let mut sql = String::new();
+                std::io::stdin().read_to_string(&mut sql)?;
+                sql
+            }
+        };
+        if sql.trim().is_empty() {
+            return Err("empty SQL query".into());
+        }
+        Ok(sql)
+    }
+}
+
+async fn connect(args: ConnectionArgs) -> CliResult<Client> {
+    let mut builder = if args.local {
+        SpiceClientBuilder::local()
+    } else {
+        SpiceClientBuilder::new()
+    };
+    if let Some(api_key) = args.api_key {
+        builder = builder.api_key(api_key);
+    }
+    if let Some(url) = args.flight_url {
+        builder = builder.flight_url(url);
+    }
+    if let Some(url) = args.firecache_url {
+        builder = builder.firecache_url(url);
+    }
+    if let Some(url) = args.http_url {
+        builder = builder.http_url(url);
+    }
+    Ok(builder.build().await?)
+}
+
+async fn run(cli: Cli) -> CliResult<()> {
+    let client = connect(cli.connection).await?;
+    match cli.command {
+        Command::Query(args) => {
+            let result = client.query(&args.read_sql()?).await?;
+            output::print_result(result, args.format).await?;
+        }
+        Command::FireQuery(args) => {
+            let result = client.fire_query(&args.read_sql()?).await?;
+            output::print_result(result, args.format).await?;
+        }
+        Command::Repl => repl::run(client).await?,
+        Command::Pairs => {
+            for pair in client.get_supported_pairs().await? {
+                println!("{pair}");
+            }
+        }
+        Command::Prices { pairs } => {
+            println!("{}", client.get_prices(&pairs).await?);
+        }
+        Command::Historical {
+            pairs,
+            start,
+            end,
+            granularity,
+        } => {
+            let prices = client
+                .get_historical_prices(&pairs, start, end, granularity)
+                .await?;
+            for (pair, data) in prices {
+                println!("{pair}");
+                for point in data {
+                    println!("  {point}");
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    // Load `.env.local` before parsing so that it can provide `API_KEY`.
+    dotenv::from_path(Path::new(".env.local")).ok();
+    let cli = Cli::parse();
+    match run(cli).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,38 @@
+use std::io::{self, BufWriter, Write};
+
+use arrow::util::pretty::pretty_format_batches;
+use clap::ValueEnum;
+use spiceai::{ExportFormat, QueryResult};
+
+use crate::CliResult;
+
+/// How query results are printed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// An aligned text table.
+    Table,
+    /// Comma-separated values with a header row.
+    Csv,
+    /// Newline-delimited JSON, one object per row.
+    Json,
+}
+
+/// Prints `result` to standard output. CSV and JSON are streamed batch by
+/// batch; tables are aligned across the whole result, so it is read first.
+pub async fn print_result(result: QueryResult, format: OutputFormat) -> CliResult<()> {
+    let mut stdout = BufWriter::new(io::stdout());
+    match format {
+        OutputFormat::Table => {
+            let batches = result.collect_batches().await?;
+            writeln!(stdout, "{}", pretty_format_batches(&batches)?)?;
+            stdout.flush()?;
+        }
+        OutputFormat::Csv => {
+            result.write_to(ExportFormat::Csv, stdout).await?;
+        }
+        OutputFormat::Json => {
+            result.write_to(ExportFormat::NdJson, stdout).await?;
+        }
+    }
+    Ok(())
+}
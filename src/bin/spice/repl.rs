@@ -0,0 +1,305 @@
+//! The interactive SQL shell started by `spice repl`.
+
+use std::collections::BTreeSet;
+use std::io::{IsTerminal, Write};
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use arrow::array::StringArray;
+use arrow::record_batch::RecordBatch;
+use arrow::util::pretty::pretty_format_batches;
+use rustyline::completion::{Completer, Pair};
+use rustyline::error::ReadlineError;
+use rustyline::history::DefaultHistory;
+use rustyline::validate::{ValidationContext, ValidationResult, Validator};
+use rustyline::{Context, Editor, Helper, Highlighter, Hinter};
+use spiceai::{Client, TableFilter};
+
+use crate::CliResult;
+
+const HELP: &str = "\
+Statements end with `;` and may span several lines.
+
+  \\describe TABLE     show the columns of TABLE (alias \\d)
+  \\firecache [on|off] send queries to the Firecache endpoint
+  \\timing [on|off]    show how long each query takes
+  \\help               show this help (alias \\?)
+  \\quit               leave the shell (alias \\q, Ctrl-D)";
+
+const COMMANDS: &[&str] = &["\\describe", "\\firecache", "\\timing", "\\help", "\\quit"];
+
+const KEYWORDS: &[&str] = &[
+    "SELECT", "FROM", "WHERE", "GROUP", "BY", "ORDER", "LIMIT", "OFFSET", "JOIN", "LEFT", "INNER",
+    "ON", "AS", "AND", "OR", "NOT", "IN", "IS", "NULL", "LIKE", "BETWEEN", "DISTINCT", "HAVING",
+    "UNION", "WITH", "CASE", "WHEN", "THEN", "ELSE", "END", "ASC", "DESC", "COUNT", "SUM", "AVG",
+    "MIN", "MAX",
+];
+
+/// Completes commands, SQL keywords and the table and column names seen so far.
+#[derive(Helper, Hinter, Highlighter)]
+struct SqlHelper {
+    names: BTreeSet<String>,
+}
+
+impl Completer for SqlHelper {
+    type Candidate = Pair;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<Pair>)> {
+        let is_word = |c: char| c.is_alphanumeric() || matches!(c, '_' | '.' | '\\');
+        let start = line[..pos]
+            .char_indices()
+            .rev()
+            .find(|(_, c)| !is_word(*c))
+            .map_or(0, |(i, c)| i + c.len_utf8());
+        let word = line[start..pos].to_lowercase();
+        if word.is_empty() {
+            return Ok((start, Vec::new()));
+        }
+        let candidates = COMMANDS
+            .iter()
+            .chain(KEYWORDS)
+            .map(|s| (*s).to_string())
+            .chain(self.names.iter().cloned())
+            .filter(|candidate| candidate.to_lowercase().starts_with(&word))
+            .map(|candidate| Pair {
+                display: candidate.clone(),
+                replacement: candidate,
+            })
+            .collect();
+        Ok((start, candidates))
+    }
+}
+
+impl Validator for SqlHelper {
+    /// Keeps reading lines until the statement is terminated by `;`.
+    fn validate(&self, ctx: &mut ValidationContext<'_>) -> rustyline::Result<ValidationResult> {
+        let input = ctx.input().trim();
+        if input.is_empty()
+            || input.starts_with('\\')
+            || input.ends_with(';')
+            || matches!(input, "exit" | "quit")
+        {
+            Ok(ValidationResult::Valid(None))
+        } else {
+            Ok(ValidationResult::Incomplete)
+        }
+    }
+}
+
+struct Session {
+    client: Client,
+    timing: bool,
+    firecache: bool,
+}
+
+/// Runs the shell until the user quits. The client, and so its channels and
+/// cached token, is shared by every statement of the session.
+pub async fn run(client: Client) -> CliResult<()> {
+    let mut editor: Editor<SqlHelper, DefaultHistory> = Editor::new()?;
+    editor.set_helper(Some(SqlHelper {
+        names: table_names(&client).await,
+    }));
+    let history = history_path();
+    if let Some(path) = &history {
+        // The history file does not exist on first use.
+        let _ = editor.load_history(path);
+    }
+
+    let mut session = Session {
+        client,
+        timing: false,
+        firecache: false,
+    };
+    println!("Connected. Type \\help for help.");
+    loop {
+        let prompt = if session.firecache {
+            "firecache> "
+        } else {
+            "spice> "
+        };
+        let line = match tokio::task::block_in_place(|| editor.readline(prompt)) {
+            Ok(line) => line,
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => break,
+            Err(e) => return Err(e.into()),
+        };
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        editor.add_history_entry(line)?;
+        match session.handle(line, editor.helper_mut()).await {
+            Ok(true) => break,
+            Ok(false) => {}
+            Err(e) => eprintln!("error: {e}"),
+        }
+    }
+
+    if let Some(path) = &history {
+        editor.save_history(path)?;
+    }
+    Ok(())
+}
+
+impl Session {
+    /// Runs a statement or shell command, returning whether to exit.
+    async fn handle(&mut self, line: &str, helper: Option<&mut SqlHelper>) -> CliResult<bool> {
+        if matches!(line, "exit" | "quit") {
+            return Ok(true);
+        }
+        let Some(command) = line.strip_prefix('\\') else {
+            self.query(line).await?;
+            return Ok(false);
+        };
+
+        let mut args = command.split_whitespace();
+        match (args.next(), args.next()) {
+            (Some("q" | "quit"), _) => return Ok(true),
+            (Some("?" | "help"), _) => println!("{HELP}"),
+            (Some("timing"), arg) => {
+                self.timing = toggle(arg, self.timing)?;
+                println!("Timing is {}.", on_off(self.timing));
+            }
+            (Some("firecache"), arg) => {
+                self.firecache = toggle(arg, self.firecache)?;
+                println!("Firecache is {}.", on_off(self.firecache));
+            }
+            (Some("d" | "describe"), Some(table)) => self.describe(table, helper).await?,
+            (Some("d" | "describe"), None) => return Err("usage: \\describe TABLE".into()),
+            _ => return Err(format!("unknown command \\{command}, try \\help").into()),
+        }
+        Ok(false)
+    }
+
+    async fn query(&self, sql: &str) -> CliResult<()> {
+        let started = Instant::now();
+        let result = if self.firecache {
+            self.client.fire_query(sql).await?
+        } else {
+            self.client.query(sql).await?
+        };
+        let batches = result.collect_batches().await?;
+        let elapsed = started.elapsed();
+
+        let rows: usize = batches.iter().map(RecordBatch::num_rows).sum();
+        page(&pretty_format_batches(&batches)?.to_string())?;
+        println!("({rows} {})", if rows == 1 { "row" } else { "rows" });
+        if self.timing {
+            println!("Time: {:.3}s", elapsed.as_secs_f64());
+        }
+        Ok(())
+    }
+
+    async fn describe(&self, table: &str, helper: Option<&mut SqlHelper>) -> CliResult<()> {
+        let table = table.trim_end_matches(';');
+        // Not every endpoint supports Flight SQL catalog commands.
+        let schema = match self.client.table_schema(table).await {
+            Ok(schema) => schema,
+            Err(_) => {
+                self.client
+                    .query_schema(&format!("SELECT * FROM {table} LIMIT 0"))
+                    .await?
+            }
+        };
+
+        let fields = schema.fields();
+        let columns = RecordBatch::try_from_iter([
+            (
+                "column",
+                Arc::new(StringArray::from_iter_values(
+                    fields.iter().map(|f| f.name().clone()),
+                )) as _,
+            ),
+            (
+                "type",
+                Arc::new(StringArray::from_iter_values(
+                    fields.iter().map(|f| f.data_type().to_string()),
+                )) as _,
+            ),
+            (
+                "nullable",
+                Arc::new(StringArray::from_iter_values(
+                    fields.iter().map(|f| f.is_nullable().to_string()),
+                )) as _,
+            ),
+        ])?;
+        println!("{}", pretty_format_batches(&[columns])?);
+
+        if let Some(helper) = helper {
+            helper.names.insert(table.to_string());
+            helper.names.extend(fields.iter().map(|f| f.name().clone()));
+        }
+        Ok(())
+    }
+}
+
+fn toggle(arg: Option<&str>, current: bool) -> CliResult<bool> {
+    match arg {
+        None => Ok(!current),
+        Some("on") => Ok(true),
+        Some("off") => Ok(false),
+        Some(arg) => Err(format!("expected on or off, got {arg}").into()),
+    }
+}
+
+fn on_off(value: bool) -> &'static str {
+    if value {
+        "on"
+    } else {
+        "off"
+    }
+}
+
+/// Lists the tables of the endpoint for completion, if it supports Flight
+/// SQL catalog commands.
+async fn table_names(client: &Client) -> BTreeSet<String> {
+    let tables = tokio::time::timeout(
+        Duration::from_secs(5),
+        client.list_tables(&TableFilter::new()),
+    )
+    .await;
+    match tables {
+        Ok(Ok(tables)) => tables
+            .iter()
+            .flat_map(|table| [table.name.clone(), table.qualified_name()])
+            .collect(),
+        _ => BTreeSet::new(),
+    }
+}
+
+fn history_path() -> Option<PathBuf> {
+    std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".spice_history"))
+}
+
+/// Prints `text` through `$PAGER` (by default `less -FRX`, which exits at
+/// once when the text fits on screen) when writing to a terminal.
+fn page(text: &str) -> CliResult<()> {
+    let pager = std::env::var("PAGER").unwrap_or_else(|_| "less -FRX".to_string());
+    if pager.is_empty() || !std::io::stdout().is_terminal() {
+        println!("{text}");
+        return Ok(());
+    }
+    match Command::new("sh")
+        .arg("-c")
+        .arg(&pager)
+        .stdin(Stdio::piped())
+        .spawn()
+    {
+        Ok(mut child) => {
+            if let Some(mut stdin) = child.stdin.take() {
+                // The pager may be closed before reading everything.
+                let _ = writeln!(stdin, "{text}");
+            }
+            child.wait()?;
+        }
+        Err(_) => println!("{text}"),
+    }
+    Ok(())
+}
@@ -0,0 +1,550 @@
+//! Record and replay of Flight and prices traffic.
+//!
+//! A [`Cassette`] attached to a client with
+//! [`SpiceClientBuilder::cassette`](crate::SpiceClientBuilder::cassette)
+//! either records every query result, `FlightInfo` and schema lookup, Flight
+//! action, upload and prices response the client receives, or serves them
+//! back from a file recorded earlier without touching the network. Only the
+//! handshake is not recorded, as replayed requests need no authentication.
+//!
+//! Query results are stored as base64-encoded Arrow IPC streams, `FlightInfo`
+//! and schema responses as base64-encoded protobuf messages and prices
+//! responses as their JSON bodies. Requests are matched on their Flight
+//! descriptor, action or HTTP path and query, and identical requests replay
+//! their responses in the order they were recorded.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use arrow::datatypes::Schema;
+use arrow::ipc::reader::StreamReader;
+use arrow::ipc::writer::StreamWriter;
+use arrow::record_batch::RecordBatch;
+use arrow_flight::{Action, FlightData, FlightDescriptor, FlightInfo, PutResult, SchemaResult};
+use base64::prelude::BASE64_STANDARD;
+use base64::Engine;
+use bytes::Bytes;
+use futures::stream::{self, StreamExt};
+use prost::Message;
+use serde::{Deserialize, Serialize};
+use tonic::metadata::{AsciiMetadataKey, MetadataMap};
+
+use crate::error::{Error, Result};
+use crate::result::{QueryResult, SharedTrailers};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Record,
+    Replay,
+}
+
+/// A recorded request and its response.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+enum Interaction {
+    Query {
+        /// Base64 of the Flight descriptor command.
+        command: String,
+        headers: BTreeMap<String, String>,
+        trailers: BTreeMap<String, String>,
+        total_records: Option<u64>,
+        total_bytes: Option<u64>,
+        /// Base64 of the result as an Arrow IPC stream.
+        batches: String,
+    },
+    FlightInfo {
+        /// Base64 of the Flight descriptor command.
+        command: String,
+        /// Base64 of the `FlightInfo` message.
+        info: String,
+    },
+    Schema {
+        /// Base64 of the Flight descriptor command.
+        command: String,
+        /// Base64 of the `SchemaResult` message.
+        schema: String,
+    },
+    Action {
+        action_type: String,
+        /// Base64 of the action body.
+        body: String,
+        /// Base64 of each result body.
+        results: Vec<String>,
+    },
+    Put {
+        /// Base64 of the Flight descriptor command.
+        command: String,
+        /// Base64 of the uploaded Flight data, length-delimited.
+        data: String,
+        /// Base64 of the application metadata of each acknowledgement.
+        results: Vec<String>,
+    },
+    Http {
+        method: String,
+        /// The request path and query, without the base URL.
+        path: String,
+        status: u16,
+        headers: BTreeMap<String, String>,
+        body: String,
+    },
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CassetteFile {
+    interactions: Vec<Interaction>,
+}
+
+#[derive(Debug)]
+struct State {
+    mode: Mode,
+    path: PathBuf,
+    interactions: Vec<Interaction>,
+    played: Vec<bool>,
+}
+
+/// Records client traffic to a file, or replays it from one.
+///
+/// Cloning is cheap: clones share the recorded interactions, so the same
+/// cassette can be attached to every endpoint of a client.
+/// ```no_run
+/// use spiceai::testing::Cassette;
+/// use spiceai::SpiceClientBuilder;
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// // Once, against the real service:
+/// let cassette = Cassette::record("tests/cassettes/blocks.json");
+/// let client = SpiceClientBuilder::new()
+///     .api_key("API_KEY")
+///     .cassette(cassette.clone())
+///     .build()
+///     .await
+///     .unwrap();
+/// client.query("SELECT * FROM eth.recent_blocks LIMIT 10").await.unwrap();
+/// cassette.save().unwrap();
+///
+/// // In tests, without network access:
+/// let cassette = Cassette::replay("tests/cassettes/blocks.json").unwrap();
+/// let client = SpiceClientBuilder::new()
+///     .cassette(cassette)
+///     .build()
+///     .await
+///     .unwrap();
+/// client.query("SELECT * FROM eth.recent_blocks LIMIT 10").await.unwrap();
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct Cassette {
+    state: Arc<Mutex<State>>,
+}
+
+impl fmt::Debug for Cassette {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = self.state();
+        f.debug_struct("Cassette")
+            .field("mode", &state.mode)
+            .field("path", &state.path)
+            .field("interactions", &state.interactions.len())
+            .finish()
+    }
+}
+
+impl Cassette {
+    /// Creates an empty cassette that records traffic, to be written to
+    /// `path` by [`save`](Self::save).
+    pub fn record(path: impl Into<PathBuf>) -> Self {
+        Self::new(Mode::Record, path.into(), Vec::new())
+    }
+
+    /// Loads a cassette recorded earlier to serve its responses. Requests it
+    /// holds no response for fail with [`Error::Cassette`].
+    pub fn replay(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let file = std::fs::read(path).map_err(|e| {
+            Error::config_with_source(format!("Cannot read cassette {}", path.display()), e)
+        })?;
+        let file: CassetteFile = serde_json::from_slice(&file).map_err(|e| {
+            Error::config_with_source(format!("Invalid cassette {}", path.display()), e)
+        })?;
+        Ok(Self::new(
+            Mode::Replay,
+            path.to_path_buf(),
+            file.interactions,
+        ))
+    }
+
+    fn new(mode: Mode, path: PathBuf, interactions: Vec<Interaction>) -> Self {
+        let played = vec![false; interactions.len()];
+        Self {
+            state: Arc::new(Mutex::new(State {
+                mode,
+                path,
+                interactions,
+                played,
+            })),
+        }
+    }
+
+    /// Writes the recorded interactions to the cassette's path, creating
+    /// parent directories as needed.
+    pub fn save(&self) -> Result<()> {
+        let state = self.state();
+        let path = &state.path;
+        let file = serde_json::to_vec_pretty(&CassetteFile {
+            interactions: state.interactions.clone(),
+        })
+        .map_err(Error::export)?;
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).map_err(Error::export)?;
+        }
+        std::fs::write(path, file).map_err(Error::export)
+    }
+
+    /// Returns whether responses are served from the cassette.
+    #[must_use]
+    pub fn is_replaying(&self) -> bool {
+        self.state().mode == Mode::Replay
+    }
+
+    /// Returns how many recorded interactions have not been replayed yet.
+    #[must_use]
+    pub fn unplayed(&self) -> usize {
+        self.state()
+            .played
+            .iter()
+            .filter(|played| !**played)
+            .count()
+    }
+
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.state.lock().expect("cassette lock poisoned")
+    }
+
+    fn push(&self, interaction: Interaction) {
+        let mut state = self.state();
+        state.interactions.push(interaction);
+        state.played.push(true);
+    }
+
+    /// Marks the first unplayed interaction accepted by `matches` as played
+    /// and returns it.
+    fn take(
+        &self,
+        request: impl fmt::Display,
+        matches: impl Fn(&Interaction) -> bool,
+    ) -> Result<Interaction> {
+        let mut state = self.state();
+        let State {
+            interactions,
+            played,
+            path,
+            ..
+        } = &mut *state;
+        let index = interactions
+            .iter()
+            .zip(played.iter())
+            .position(|(interaction, played)| !played && matches(interaction))
+            .ok_or_else(|| {
+                Error::Cassette(format!(
+                    "No recorded response for {request} in {}",
+                    path.display()
+                ))
+            })?;
+        played[index] = true;
+        Ok(interactions[index].clone())
+    }
+
+    /// Replays the result of the query described by `descriptor`.
+    pub(crate) fn replay_query(&self, descriptor: &FlightDescriptor) -> Result<QueryResult> {
+        let command = BASE64_STANDARD.encode(&descriptor.cmd);
+        let request = describe("query", descriptor);
+        let Interaction::Query {
+            headers,
+            trailers,
+            total_records,
+            total_bytes,
+            batches,
+            ..
+        } = self.take(request, |interaction| {
+            matches!(interaction, Interaction::Query { command: c, .. } if *c == command)
+        })?
+        else {
+            unreachable!("matched a query interaction");
+        };
+
+        let reader = StreamReader::try_new(std::io::Cursor::new(decode_base64(&batches)?), None)?;
+        let schema = reader.schema();
+        let batches = reader.collect::<std::result::Result<Vec<_>, _>>()?;
+        Ok(QueryResult::new(
+            stream::iter(batches.into_iter().map(Ok)).boxed(),
+            Some(schema),
+            total_records,
+            total_bytes,
+            to_metadata(&headers),
+            Arc::new(Mutex::new(Some(to_metadata(&trailers)))),
+        ))
+    }
+
+    /// Reads `result` to its end, records it for `descriptor` and returns an
+    /// equivalent result over the recorded batches.
+    pub(crate) async fn record_query(
+        &self,
+        descriptor: &FlightDescriptor,
+        mut result: QueryResult,
+    ) -> Result<QueryResult> {
+        let mut batches: Vec<RecordBatch> = Vec::new();
+        while let Some(batch) = result.next().await {
+            batches.push(batch?);
+        }
+        let schema = result.schema().unwrap_or_else(|| Arc::new(Schema::empty()));
+        let headers = result.headers().clone();
+        let trailers = result.trailers();
+        let (total_records, total_bytes) = (result.total_records(), result.total_bytes());
+
+        let mut ipc = Vec::new();
+        let mut writer = StreamWriter::try_new(&mut ipc, &schema)?;
+        for batch in &batches {
+            writer.write(batch)?;
+        }
+        writer.finish()?;
+        drop(writer);
+
+        self.push(Interaction::Query {
+            command: BASE64_STANDARD.encode(&descriptor.cmd),
+            headers: from_metadata(&headers),
+            trailers: trailers.as_ref().map(from_metadata).unwrap_or_default(),
+            total_records,
+            total_bytes,
+            batches: BASE64_STANDARD.encode(ipc),
+        });
+        Ok(QueryResult::new(
+            stream::iter(batches.into_iter().map(Ok)).boxed(),
+            Some(schema),
+            total_records,
+            total_bytes,
+            headers,
+            SharedTrailers::new(Mutex::new(trailers)),
+        ))
+    }
+
+    /// Replays the `FlightInfo` returned for `descriptor`.
+    pub(crate) fn replay_flight_info(&self, descriptor: &FlightDescriptor) -> Result<FlightInfo> {
+        let command = BASE64_STANDARD.encode(&descriptor.cmd);
+        let Interaction::FlightInfo { info, .. } =
+            self.take(describe("flight info", descriptor), |interaction| {
+                matches!(interaction, Interaction::FlightInfo { command: c, .. } if *c == command)
+            })?
+        else {
+            unreachable!("matched a flight info interaction");
+        };
+        decode_message(&info)
+    }
+
+    /// Records the `FlightInfo` returned for `descriptor`.
+    pub(crate) fn record_flight_info(&self, descriptor: &FlightDescriptor, info: &FlightInfo) {
+        self.push(Interaction::FlightInfo {
+            command: BASE64_STANDARD.encode(&descriptor.cmd),
+            info: BASE64_STANDARD.encode(info.encode_to_vec()),
+        });
+    }
+
+    /// Replays the schema returned for `descriptor`.
+    pub(crate) fn replay_schema(&self, descriptor: &FlightDescriptor) -> Result<SchemaResult> {
+        let command = BASE64_STANDARD.encode(&descriptor.cmd);
+        let Interaction::Schema { schema, .. } =
+            self.take(describe("schema", descriptor), |interaction| {
+                matches!(interaction, Interaction::Schema { command: c, .. } if *c == command)
+            })?
+        else {
+            unreachable!("matched a schema interaction");
+        };
+        decode_message(&schema)
+    }
+
+    /// Records the schema returned for `descriptor`.
+    pub(crate) fn record_schema(&self, descriptor: &FlightDescriptor, schema: &SchemaResult) {
+        self.push(Interaction::Schema {
+            command: BASE64_STANDARD.encode(&descriptor.cmd),
+            schema: BASE64_STANDARD.encode(schema.encode_to_vec()),
+        });
+    }
+
+    /// Replays the results of `action`.
+    pub(crate) fn replay_action(&self, action: &Action) -> Result<Vec<arrow_flight::Result>> {
+        let body = BASE64_STANDARD.encode(&action.body);
+        let Interaction::Action { results, .. } =
+            self.take(format!("action {}", action.r#type), |interaction| {
+                matches!(interaction, Interaction::Action { action_type, body: b, .. }
+                    if *action_type == action.r#type && *b == body)
+            })?
+        else {
+            unreachable!("matched an action interaction");
+        };
+        results
+            .iter()
+            .map(|result| {
+                Ok(arrow_flight::Result {
+                    body: Bytes::from(decode_base64(result)?),
+                })
+            })
+            .collect()
+    }
+
+    /// Records the results of `action`.
+    pub(crate) fn record_action(&self, action: &Action, results: &[arrow_flight::Result]) {
+        self.push(Interaction::Action {
+            action_type: action.r#type.clone(),
+            body: BASE64_STANDARD.encode(&action.body),
+            results: results
+                .iter()
+                .map(|result| BASE64_STANDARD.encode(&result.body))
+                .collect(),
+        });
+    }
+
+    /// Replays the acknowledgements of uploading `data`.
+    pub(crate) fn replay_put(&self, data: &[FlightData]) -> Result<Vec<PutResult>> {
+        let (command, data) = encode_put(data);
+        let Interaction::Put { results, .. } =
+            self.take(format!("put {command}"), |interaction| {
+                matches!(interaction, Interaction::Put { command: c, data: d, .. }
+                    if *c == command && *d == data)
+            })?
+        else {
+            unreachable!("matched a put interaction");
+        };
+        results
+            .iter()
+            .map(|result| {
+                Ok(PutResult {
+                    app_metadata: Bytes::from(decode_base64(result)?),
+                })
+            })
+            .collect()
+    }
+
+    /// Records the acknowledgements of uploading `data`.
+    pub(crate) fn record_put(&self, data: &[FlightData], results: &[PutResult]) {
+        let (command, data) = encode_put(data);
+        self.push(Interaction::Put {
+            command,
+            data,
+            results: results
+                .iter()
+                .map(|result| BASE64_STANDARD.encode(&result.app_metadata))
+                .collect(),
+        });
+    }
+
+    /// Sends `request` with `client`, or replays its response.
+    pub(crate) async fn send(
+        &self,
+        client: &reqwest::Client,
+        request: reqwest::Request,
+    ) -> Result<reqwest::Response> {
+        let method = request.method().to_string();
+        let url = request.url();
+        let path = match url.query() {
+            Some(query) => format!("{}?{query}", url.path()),
+            None => url.path().to_string(),
+        };
+
+        let (status, headers, body) = if self.is_replaying() {
+            let Interaction::Http {
+                status,
+                headers,
+                body,
+                ..
+            } = self.take(format!("{method} {path}"), |interaction| {
+                matches!(interaction, Interaction::Http { method: m, path: p, .. }
+                    if *m == method && *p == path)
+            })?
+            else {
+                unreachable!("matched an HTTP interaction");
+            };
+            (status, headers, body)
+        } else {
+            let response = client.execute(request).await?;
+            let status = response.status().as_u16();
+            let headers: BTreeMap<String, String> = response
+                .headers()
+                .iter()
+                .filter_map(|(name, value)| {
+                    Some((name.to_string(), value.to_str().ok()?.to_string()))
+                })
+                .collect();
+            let body = response.text().await?;
+            self.push(Interaction::Http {
+                method,
+                path,
+                status,
+                headers: headers.clone(),
+                body: body.clone(),
+            });
+            (status, headers, body)
+        };
+
+        let mut response = hyper::Response::builder().status(status);
+        for (name, value) in &headers {
+            response = response.header(name, value);
+        }
+        let response = response
+            .body(body)
+            .map_err(|e| Error::Cassette(format!("Invalid recorded response: {e}")))?;
+        Ok(reqwest::Response::from(response))
+    }
+}
+
+/// Encodes the descriptor command of an upload, and the upload itself.
+fn encode_put(data: &[FlightData]) -> (String, String) {
+    let command = data
+        .iter()
+        .find_map(|data| data.flight_descriptor.as_ref())
+        .map(|descriptor| BASE64_STANDARD.encode(&descriptor.cmd))
+        .unwrap_or_default();
+    let mut encoded = Vec::new();
+    for data in data {
+        encoded.extend(data.encode_length_delimited_to_vec());
+    }
+    (command, BASE64_STANDARD.encode(encoded))
+}
+
+/// Describes a request for `descriptor` in errors.
+fn describe(kind: &str, descriptor: &FlightDescriptor) -> String {
+    format!(
+        "{kind} {:?}",
+        String::from_utf8_lossy(&descriptor.cmd).trim_matches(char::is_control)
+    )
+}
+
+fn decode_message<M: Message + Default>(data: &str) -> Result<M> {
+    M::decode(decode_base64(data)?.as_slice())
+        .map_err(|e| Error::Cassette(format!("Invalid recorded message: {e}")))
+}
+
+fn decode_base64(data: &str) -> Result<Vec<u8>> {
+    BASE64_STANDARD
+        .decode(data)
+        .map_err(|e| Error::Cassette(format!("Invalid recorded data: {e}")))
+}
+
+fn from_metadata(metadata: &MetadataMap) -> BTreeMap<String, String> {
+    metadata
+        .clone()
+        .into_headers()
+        .iter()
+        .filter_map(|(name, value)| Some((name.to_string(), value.to_str().ok()?.to_string())))
+        .collect()
+}
+
+fn to_metadata(headers: &BTreeMap<String, String>) -> MetadataMap {
+    let mut metadata = MetadataMap::new();
+    for (name, value) in headers {
+        if let (Ok(name), Ok(value)) = (name.parse::<AsciiMetadataKey>(), value.parse()) {
+            metadata.insert(name, value);
+        }
+    }
+    metadata
+}
@@ -0,0 +1,256 @@
+use std::sync::Arc;
+
+use arrow::array::{Array, AsArray};
+use arrow::datatypes::SchemaRef;
+use arrow::ipc::convert::try_schema_from_ipc_buffer;
+use arrow::record_batch::RecordBatch;
+use arrow_flight::sql::{
+    CommandGetCatalogs, CommandGetDbSchemas, CommandGetTables, ProstMessageExt,
+};
+use arrow_flight::FlightDescriptor;
+use prost::Message;
+
+use crate::error::{Error, Result};
+use crate::flight::SqlFlightClient;
+use crate::options::QueryOptions;
+
+/// A database schema returned by [`Client::list_schemas`](crate::Client::list_schemas).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DatabaseSchema {
+    pub catalog: Option<String>,
+    pub name: String,
+}
+
+/// A table returned by [`Client::list_tables`](crate::Client::list_tables).
+#[derive(Debug, Clone, PartialEq)]
+pub struct TableInfo {
+    pub catalog: Option<String>,
+    pub schema: Option<String>,
+    pub name: String,
+    /// The kind of table, e.g. `TABLE` or `VIEW`.
+    pub table_type: String,
+    /// The Arrow schema of the table, set when requested with
+    /// [`TableFilter::with_arrow_schema`].
+    pub arrow_schema: Option<SchemaRef>,
+}
+
+impl TableInfo {
+    /// Returns the dot-separated, fully qualified table name.
+    #[must_use]
+    pub fn qualified_name(&self) -> String {
+        [
+            self.catalog.as_deref(),
+            self.schema.as_deref(),
+            Some(&self.name),
+        ]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>()
+        .join(".")
+    }
+}
+
+/// Restricts the tables returned by [`Client::list_tables`](crate::Client::list_tables).
+///
+/// Schema and table patterns use SQL `LIKE` syntax: `%` matches any sequence
+/// of characters and `_` matches any single character.
+/// ```
+/// use spiceai::TableFilter;
+///
+/// let filter = TableFilter::new()
+///     .with_schema_pattern("eth")
+///     .with_table_pattern("%blocks")
+///     .with_arrow_schema(true);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct TableFilter {
+    catalog: Option<String>,
+    schema_pattern: Option<String>,
+    table_pattern: Option<String>,
+    table_types: Vec<String>,
+    include_schema: bool,
+}
+
+impl TableFilter {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only returns tables from `catalog`.
+    #[must_use]
+    pub fn with_catalog(mut self, catalog: impl Into<String>) -> Self {
+        self.catalog = Some(catalog.into());
+        self
+    }
+
+    /// Only returns tables whose schema name matches `pattern`.
+    #[must_use]
+    pub fn with_schema_pattern(mut self, pattern: impl Into<String>) -> Self {
+        self.schema_pattern = Some(pattern.into());
+        self
+    }
+
+    /// Only returns tables whose name matches `pattern`.
+    #[must_use]
+    pub fn with_table_pattern(mut self, pattern: impl Into<String>) -> Self {
+        self.table_pattern = Some(pattern.into());
+        self
+    }
+
+    /// Only returns tables of the given types, e.g. `TABLE` or `VIEW`.
+    #[must_use]
+    pub fn with_table_types<S: Into<String>>(mut self, types: impl IntoIterator<Item = S>) -> Self {
+        self.table_types = types.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Includes the Arrow schema of each table in the results.
+    #[must_use]
+    pub fn with_arrow_schema(mut self, include_schema: bool) -> Self {
+        self.include_schema = include_schema;
+        self
+    }
+}
+
+pub async fn list_catalogs(client: &SqlFlightClient) -> Result<Vec<String>> {
+    let batches = run(client, CommandGetCatalogs {}.as_any().encode_to_vec()).await?;
+    let mut catalogs = Vec::new();
+    for batch in &batches {
+        catalogs.extend(string_column(batch, "catalog_name")?.into_iter().flatten());
+    }
+    Ok(catalogs)
+}
+
+pub async fn list_schemas(
+    client: &SqlFlightClient,
+    catalog: Option<&str>,
+    schema_pattern: Option<&str>,
+) -> Result<Vec<DatabaseSchema>> {
+    let cmd = CommandGetDbSchemas {
+        catalog: catalog.map(str::to_string),
+        db_schema_filter_pattern: schema_pattern.map(str::to_string),
+    };
+    let batches = run(client, cmd.as_any().encode_to_vec()).await?;
+    let mut schemas = Vec::new();
+    for batch in &batches {
+        let catalogs = string_column(batch, "catalog_name")?;
+        let names = string_column(batch, "db_schema_name")?;
+        for (catalog, name) in catalogs.into_iter().zip(names) {
+            schemas.push(DatabaseSchema {
+                catalog,
+                name: name.unwrap_or_default(),
+            });
+        }
+    }
+    Ok(schemas)
+}
+
+pub async fn list_tables(client: &SqlFlightClient, filter: &TableFilter) -> Result<Vec<TableInfo>> {
+    let cmd = CommandGetTables {
+        catalog: filter.catalog.clone(),
+        db_schema_filter_pattern: filter.schema_pattern.clone(),
+        table_name_filter_pattern: filter.table_pattern.clone(),
+        table_types: filter.table_types.clone(),
+        include_schema: filter.include_schema,
+    };
+    let batches = run(client, cmd.as_any().encode_to_vec()).await?;
+    let mut tables = Vec::new();
+    for batch in &batches {
+        let catalogs = string_column(batch, "catalog_name")?;
+        let schemas = string_column(batch, "db_schema_name")?;
+        let names = string_column(batch, "table_name")?;
+        let types = string_column(batch, "table_type")?;
+        let arrow_schemas = if filter.include_schema {
+            schema_column(batch, "table_schema")?
+        } else {
+            vec![None; batch.num_rows()]
+        };
+        for i in 0..batch.num_rows() {
+            tables.push(TableInfo {
+                catalog: catalogs[i].clone(),
+                schema: schemas[i].clone(),
+                name: names[i].clone().unwrap_or_default(),
+                table_type: types[i].clone().unwrap_or_default(),
+                arrow_schema: arrow_schemas[i].clone(),
+            });
+        }
+    }
+    Ok(tables)
+}
+
+/// Looks up the Arrow schema of `name`, given as `table`, `schema.table` or
+/// `catalog.schema.table`.
+pub async fn table_schema(client: &SqlFlightClient, name: &str) -> Result<SchemaRef> {
+    let parts: Vec<&str> = name.split('.').collect();
+    let (catalog, schema, table) = match parts.as_slice() {
+        [table] => (None, None, *table),
+        [schema, table] => (None, Some(*schema), *table),
+        [catalog, schema, table] => (Some(*catalog), Some(*schema), *table),
+        _ => {
+            return Err(Error::InvalidArgument {
+                message: format!("Invalid table name \"{name}\""),
+            })
+        }
+    };
+
+    // `_` in names is a single-character wildcard in patterns, and servers may
+    // ignore the catalog, so the results are matched exactly below.
+    let mut filter = TableFilter::new()
+        .with_table_pattern(table)
+        .with_arrow_schema(true);
+    if let Some(catalog) = catalog {
+        filter = filter.with_catalog(catalog);
+    }
+    if let Some(schema) = schema {
+        filter = filter.with_schema_pattern(schema);
+    }
+
+    list_tables(client, &filter)
+        .await?
+        .into_iter()
+        .find(|t| {
+            t.name == table
+                && (schema.is_none() || t.schema.as_deref() == schema)
+                && (catalog.is_none() || t.catalog.as_deref() == catalog)
+        })
+        .and_then(|t| t.arrow_schema)
+        .ok_or_else(|| Error::InvalidArgument {
+            message: format!("Table \"{name}\" not found"),
+        })
+}
+
+async fn run(client: &SqlFlightClient, cmd: Vec<u8>) -> Result<Vec<RecordBatch>> {
+    client
+        .execute_with_options(FlightDescriptor::new_cmd(cmd), &QueryOptions::default())
+        .await?
+        .collect_batches()
+        .await
+}
+
+fn string_column(batch: &RecordBatch, name: &str) -> Result<Vec<Option<String>>> {
+    let column = batch
+        .column_by_name(name)
+        .ok_or_else(|| Error::decode(format!("Missing column \"{name}\"")))?;
+    let values = column
+        .as_string_opt::<i32>()
+        .ok_or_else(|| Error::decode(format!("Column \"{name}\" is not a string column")))?;
+    Ok(values.iter().map(|v| v.map(str::to_string)).collect())
+}
+
+fn schema_column(batch: &RecordBatch, name: &str) -> Result<Vec<Option<SchemaRef>>> {
+    let column = batch
+        .column_by_name(name)
+        .ok_or_else(|| Error::decode(format!("Missing column \"{name}\"")))?;
+    let values = column
+        .as_binary_opt::<i32>()
+        .ok_or_else(|| Error::decode(format!("Column \"{name}\" is not a binary column")))?;
+    (0..values.len())
+        .map(|i| {
+            if values.is_null(i) {
+                return Ok(None);
+            }
+            Ok(Some(Arc::new(try_schema_from_ipc_buffer(values.value(i))?)))
+        })
+        .collect()
+}
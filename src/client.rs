@@ -1,49 +1,344 @@
+#[cfg(feature = "test-util")]
+use crate::{cassette::Cassette, tls::flight_endpoint};
 use crate::{
-    config::{FIRECACHE_ADDR, FLIGHT_ADDR, HTTPS_ADDR},
-    flight::SqlFlightClient,
-    prices::PricesClient,
-    tls::new_tls_flight_channel,
-    HistoricalPriceData, LatestPricesResponse,
+    catalog::{self, DatabaseSchema, TableFilter, TableInfo},
+    config::{FIRECACHE_ADDR, FLIGHT_ADDR, HTTPS_ADDR, LOCAL_FLIGHT_ADDR, LOCAL_HTTP_ADDR},
+    error::{Error, Result},
+    export::ExportFormat,
+    flight::{AuthMetrics, QueryInfo, SqlFlightClient},
+    options::{QueryOptions, QueryProtocol},
+    prepared::PreparedStatement,
+    prices::{Granularity, PricesClient},
+    result::QueryResult,
+    retry::RetryPolicy,
+    tls::{new_tls_flight_channel, ChannelOptions, ClientIdentity, TlsRoots},
+    HistoricalPriceData, LatestPricesResponse, PriceTick, TradingPair,
 };
-use arrow_flight::decode::FlightRecordBatchStream;
+use arrow::datatypes::SchemaRef;
 use chrono::{DateTime, Utc};
-use futures::try_join;
-use std::{collections::HashMap, error::Error};
-use tonic::transport::Channel;
-
-struct SpiceClientConfig {
-    https_addr: String,
-    flight_channel: Channel,
-    firecache_channel: Channel,
+use futures::stream::BoxStream;
+use futures::{try_join, TryStreamExt};
+use serde::de::DeserializeOwned;
+use std::{collections::HashMap, fs::File, io::BufWriter, path::Path, time::Duration};
+
+/// Builder for a [`SpiceClient`], allowing each endpoint, the API key and the
+/// connection settings to be configured independently.
+///
+/// Endpoints default to the Spice.ai cloud. URLs starting with `https://` are
+/// dialed over TLS, anything else (e.g. `http://localhost:50051`) in plaintext.
+/// ```no_run
+/// use spiceai::SpiceClientBuilder;
+/// use std::time::Duration;
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let client = SpiceClientBuilder::new()
+///         .api_key("API_KEY")
+///         .flight_url("https://flight.spiceai.io")
+///         .connect_timeout(Duration::from_secs(10))
+///         .build()
+///         .await
+///         .unwrap();
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct SpiceClientBuilder {
+    api_key: Option<String>,
+    flight_url: String,
+    firecache_url: String,
+    http_url: String,
+    channel_options: ChannelOptions,
+    endpoint_concurrency: usize,
+    retry_policy: RetryPolicy,
+    query_protocol: QueryProtocol,
+    prices_concurrency: usize,
+    max_historical_intervals: Option<u32>,
+    pair_validation: Option<Duration>,
+    #[cfg(feature = "test-util")]
+    cassette: Option<Cassette>,
+}
+
+impl Default for SpiceClientBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
-impl SpiceClientConfig {
-    fn new(https_addr: String, flight_channel: Channel, firecache_channel: Channel) -> Self {
-        SpiceClientConfig {
-            https_addr,
-            flight_channel,
-            firecache_channel,
+impl SpiceClientBuilder {
+    /// Creates a builder pointed at the default Spice.ai cloud endpoints.
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            api_key: None,
+            flight_url: FLIGHT_ADDR.to_string(),
+            firecache_url: FIRECACHE_ADDR.to_string(),
+            http_url: HTTPS_ADDR.to_string(),
+            channel_options: ChannelOptions::default(),
+            endpoint_concurrency: 1,
+            retry_policy: RetryPolicy::default(),
+            query_protocol: QueryProtocol::Raw,
+            prices_concurrency: 4,
+            max_historical_intervals: None,
+            pair_validation: None,
+            #[cfg(feature = "test-util")]
+            cassette: None,
+        }
+    }
+
+    /// Creates a builder pointed at a local Spice OSS runtime
+    /// (Flight on `http://localhost:50051`, HTTP on `http://localhost:8090`).
+    ///
+    /// No API key is set, so queries skip the authentication handshake and
+    /// are sent over plaintext channels using the Flight SQL protocol.
+    /// Firecache queries go to the same Flight endpoint, as the OSS runtime
+    /// has no separate Firecache service.
+    #[must_use]
+    pub fn local() -> Self {
+        Self {
+            api_key: None,
+            flight_url: LOCAL_FLIGHT_ADDR.to_string(),
+            firecache_url: LOCAL_FLIGHT_ADDR.to_string(),
+            http_url: LOCAL_HTTP_ADDR.to_string(),
+            channel_options: ChannelOptions::default(),
+            endpoint_concurrency: 1,
+            retry_policy: RetryPolicy::default(),
+            query_protocol: QueryProtocol::FlightSql,
+            prices_concurrency: 4,
+            max_historical_intervals: None,
+            pair_validation: None,
+            #[cfg(feature = "test-util")]
+            cassette: None,
         }
     }
 
-    pub async fn load_from_default() -> Result<SpiceClientConfig, Box<dyn Error>> {
+    /// Sets the API key used to authenticate against every endpoint.
+    ///
+    /// When no API key is set, requests are sent unauthenticated.
+    #[must_use]
+    pub fn api_key(mut self, api_key: impl Into<String>) -> Self {
+        self.api_key = Some(api_key.into());
+        self
+    }
+
+    /// Sets the URL of the Flight endpoint used by [`SpiceClient::query`].
+    #[must_use]
+    pub fn flight_url(mut self, url: impl Into<String>) -> Self {
+        self.flight_url = url.into();
+        self
+    }
+
+    /// Sets the URL of the Firecache endpoint used by [`SpiceClient::fire_query`].
+    #[must_use]
+    pub fn firecache_url(mut self, url: impl Into<String>) -> Self {
+        self.firecache_url = url.into();
+        self
+    }
+
+    /// Sets the base URL of the HTTP API used for prices.
+    #[must_use]
+    pub fn http_url(mut self, url: impl Into<String>) -> Self {
+        self.http_url = url.into().trim_end_matches('/').to_string();
+        self
+    }
+
+    /// Sets the maximum time allowed to establish a connection to an endpoint.
+    #[must_use]
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.channel_options.connect_timeout = Some(timeout);
+        self
+    }
+
+    /// Sets the maximum time allowed for each request made by the client.
+    #[must_use]
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.channel_options.timeout = Some(timeout);
+        self
+    }
+
+    /// Trusts the PEM-encoded certificate authorities in `pem`, in addition
+    /// to the roots chosen with [`tls_roots`](Self::tls_roots). Use this to
+    /// connect to a self-hosted runtime whose certificate is issued by a
+    /// private CA.
+    #[must_use]
+    pub fn ca_certificate(mut self, pem: impl Into<Vec<u8>>) -> Self {
+        self.channel_options.tls.ca_certificates.push(pem.into());
+        self
+    }
+
+    /// Presents the PEM-encoded client certificate `cert` and PKCS #8
+    /// private key `key` to endpoints that require mutual TLS.
+    #[must_use]
+    pub fn client_identity(mut self, cert: impl Into<Vec<u8>>, key: impl Into<Vec<u8>>) -> Self {
+        self.channel_options.tls.identity = Some(ClientIdentity {
+            cert: cert.into(),
+            key: key.into(),
+        });
+        self
+    }
+
+    /// Sets the name sent as SNI and expected in the server certificate of
+    /// the Flight endpoints, e.g. when they are dialed by IP address.
+    /// Defaults to the host of each endpoint URL.
+    #[must_use]
+    pub fn tls_domain_name(mut self, domain_name: impl Into<String>) -> Self {
+        self.channel_options.tls.domain_name = Some(domain_name.into());
+        self
+    }
+
+    /// Sets which root certificates are trusted. Defaults to
+    /// [`TlsRoots::Native`].
+    #[must_use]
+    pub fn tls_roots(mut self, roots: TlsRoots) -> Self {
+        self.channel_options.tls.roots = roots;
+        self
+    }
+
+    /// Sets how many endpoints of a query result are fetched in parallel.
+    ///
+    /// Defaults to 1, fetching endpoints one after another. With a higher
+    /// value, batches from results the server does not mark as ordered may be
+    /// interleaved across endpoints.
+    #[must_use]
+    pub fn endpoint_concurrency(mut self, endpoint_concurrency: usize) -> Self {
+        self.endpoint_concurrency = endpoint_concurrency.max(1);
+        self
+    }
+
+    /// Sets how many historical prices requests
+    /// [`SpiceClient::stream_historical_prices`] sends in parallel. Defaults
+    /// to 4.
+    #[must_use]
+    pub fn prices_concurrency(mut self, concurrency: usize) -> Self {
+        self.prices_concurrency = concurrency.max(1);
+        self
+    }
+
+    /// Limits how many intervals a single historical prices request may span,
+    /// e.g. 1,440 for one day of [`Granularity::Minute`] prices. Longer
+    /// ranges are rejected by [`SpiceClient::get_historical_prices`] before
+    /// any request is sent, and fetched in windows of this size by
+    /// [`SpiceClient::stream_historical_prices`]. By default the range is
+    /// left for the server to check.
+    #[must_use]
+    pub fn max_historical_intervals(mut self, max_intervals: u32) -> Self {
+        self.max_historical_intervals = Some(max_intervals.max(1));
+        self
+    }
+
+    /// Checks the pairs of every prices request against
+    /// [`SpiceClient::get_supported_pairs`] before sending it, failing with
+    /// [`Error::InvalidArgument`] for unsupported pairs. The supported pairs
+    /// are cached for `cache_ttl`; `Duration::ZERO` fetches them every time.
+    #[must_use]
+    pub fn validate_pairs(mut self, cache_ttl: Duration) -> Self {
+        self.pair_validation = Some(cache_ttl);
+        self
+    }
+
+    /// Sets the policy used to retry transient Flight and prices failures.
+    /// See [`RetryPolicy`] for the defaults; use [`RetryPolicy::none`] to
+    /// disable retries.
+    #[must_use]
+    pub fn retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    /// Sets how SQL queries are encoded in Flight requests. Use
+    /// [`QueryProtocol::FlightSql`] to query any Flight SQL server.
+    #[must_use]
+    pub fn query_protocol(mut self, protocol: QueryProtocol) -> Self {
+        self.query_protocol = protocol;
+        self
+    }
+
+    /// Records the client's traffic to `cassette`, or serves it from the
+    /// cassette without connecting to any endpoint when it is replaying.
+    #[cfg(feature = "test-util")]
+    #[must_use]
+    pub fn cassette(mut self, cassette: Cassette) -> Self {
+        self.cassette = Some(cassette);
+        self
+    }
+
+    /// Connects to the configured endpoints and returns the client.
+    pub async fn build(self) -> Result<SpiceClient> {
+        #[cfg(feature = "test-util")]
+        let (flight_chan, firecache_chan) = match &self.cassette {
+            Some(cassette) if cassette.is_replaying() => (
+                flight_endpoint(&self.flight_url, &self.channel_options)?.connect_lazy(),
+                flight_endpoint(&self.firecache_url, &self.channel_options)?.connect_lazy(),
+            ),
+            _ => try_join!(
+                new_tls_flight_channel(&self.flight_url, &self.channel_options),
+                new_tls_flight_channel(&self.firecache_url, &self.channel_options)
+            )?,
+        };
+        #[cfg(not(feature = "test-util"))]
         let (flight_chan, firecache_chan) = try_join!(
-            new_tls_flight_channel(FLIGHT_ADDR),
-            new_tls_flight_channel(FIRECACHE_ADDR)
+            new_tls_flight_channel(&self.flight_url, &self.channel_options),
+            new_tls_flight_channel(&self.firecache_url, &self.channel_options)
         )?;
-
-        Ok(SpiceClientConfig::new(
-            HTTPS_ADDR.to_string(),
-            flight_chan,
-            firecache_chan,
-        ))
+        let prices = PricesClient::new(
+            Some(self.http_url),
+            self.api_key.clone(),
+            &self.channel_options,
+        )?
+        .with_retry_policy(self.retry_policy.clone())
+        .with_concurrency(self.prices_concurrency);
+        let prices = match self.max_historical_intervals {
+            Some(max_intervals) => prices.with_max_intervals(max_intervals),
+            None => prices,
+        };
+        let client = SpiceClient {
+            flight: SqlFlightClient::new(
+                &self.flight_url,
+                flight_chan,
+                self.api_key.clone(),
+                self.channel_options.clone(),
+            )
+            .with_endpoint_concurrency(self.endpoint_concurrency)
+            .with_retry_policy(self.retry_policy.clone())
+            .with_query_protocol(self.query_protocol),
+            firecache: SqlFlightClient::new(
+                &self.firecache_url,
+                firecache_chan,
+                self.api_key.clone(),
+                self.channel_options.clone(),
+            )
+            .with_endpoint_concurrency(self.endpoint_concurrency)
+            .with_retry_policy(self.retry_policy.clone())
+            .with_query_protocol(self.query_protocol),
+            prices,
+        };
+        let client = match self.pair_validation {
+            Some(cache_ttl) => SpiceClient {
+                prices: client.prices.with_pair_validation(cache_ttl),
+                ..client
+            },
+            None => client,
+        };
+        #[cfg(feature = "test-util")]
+        if let Some(cassette) = self.cassette {
+            return Ok(SpiceClient {
+                flight: client.flight.with_cassette(cassette.clone()),
+                firecache: client.firecache.with_cassette(cassette.clone()),
+                prices: client.prices.with_cassette(cassette),
+            });
+        }
+        Ok(client)
     }
 }
 
 /// The `SpiceClient` is the main entry point for interacting with the Spice API.
 /// It provides methods for querying the Spice Flight and Firecache endpoints,
 /// as well as the Spice Prices endpoint.
+///
+/// `SpiceClient` is `Send + Sync` and cheap to clone: clones share the same
+/// channels and authentication state, so a single client can be used from
+/// many tasks to run queries concurrently.
 #[allow(clippy::module_name_repetitions)]
+#[derive(Clone)]
 pub struct SpiceClient {
     flight: SqlFlightClient,
     firecache: SqlFlightClient,
@@ -57,17 +352,32 @@ impl SpiceClient {
     ///
     /// #[tokio::main]
     /// async fn main() {
-    ///     let mut client = Client::new("API_KEY").await.unwrap();
+    ///     let client = Client::new("API_KEY").await.unwrap();
     /// }
     /// ```
-    pub async fn new(api_key: &str) -> Result<Self, Box<dyn Error>> {
-        let config = SpiceClientConfig::load_from_default().await?;
+    pub async fn new(api_key: &str) -> Result<Self> {
+        SpiceClientBuilder::new().api_key(api_key).build().await
+    }
+
+    /// Creates a new `SpiceClient` connected to a local, unauthenticated Spice
+    /// OSS runtime. See [`SpiceClientBuilder::local`] to customize it.
+    /// ```no_run
+    /// use spiceai::Client;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let client = Client::local().await.unwrap();
+    /// }
+    /// ```
+    pub async fn local() -> Result<Self> {
+        SpiceClientBuilder::local().build().await
+    }
 
-        Ok(Self {
-            flight: SqlFlightClient::new(config.flight_channel, api_key.to_string()),
-            firecache: SqlFlightClient::new(config.firecache_channel, api_key.to_string()),
-            prices: PricesClient::new(Some(config.https_addr), api_key.to_string()),
-        })
+    /// Returns a [`SpiceClientBuilder`] for configuring custom endpoints,
+    /// timeouts and credentials.
+    #[must_use]
+    pub fn builder() -> SpiceClientBuilder {
+        SpiceClientBuilder::new()
     }
 
     /// Queries the Spice Flight endpoint with the given SQL query.
@@ -76,84 +386,433 @@ impl SpiceClient {
     /// #
     /// # #[tokio::main]
     /// # async fn main() {
-    /// #  let mut client = Client::new("API_KEY").await.unwrap();
+    /// #  let client = Client::new("API_KEY").await.unwrap();
     /// let data = client.query("SELECT * FROM eth.recent_blocks LIMIT 10;").await;
     /// # }
     /// ````
-    pub async fn query(&mut self, query: &str) -> Result<FlightRecordBatchStream, Box<dyn Error>> {
+    pub async fn query(&self, query: &str) -> Result<QueryResult> {
         self.flight.query(query).await
     }
 
+    /// Queries the Spice Flight endpoint with the given SQL query, bounded by
+    /// the timeout and cancellation token of `options`.
+    /// ```no_run
+    /// # use spiceai::{Client, QueryOptions};
+    /// # use std::time::Duration;
+    /// #
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// #  let client = Client::new("API_KEY").await.unwrap();
+    /// let options = QueryOptions::new().with_timeout(Duration::from_secs(30));
+    /// let data = client
+    ///     .query_with_options("SELECT * FROM eth.recent_blocks LIMIT 10;", &options)
+    ///     .await;
+    /// # }
+    /// ```
+    pub async fn query_with_options(
+        &self,
+        query: &str,
+        options: &QueryOptions,
+    ) -> Result<QueryResult> {
+        self.flight.query_with_options(query, options).await
+    }
+
+    /// Queries the Spice Flight endpoint and deserializes each result row
+    /// into a `T`, matching columns to fields by name.
+    /// ```no_run
+    /// # use spiceai::{Client, StreamExt};
+    /// # use serde::Deserialize;
+    /// #
+    /// #[derive(Deserialize)]
+    /// struct Block {
+    ///     number: i64,
+    ///     hash: String,
+    ///     miner: Option<String>,
+    /// }
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// #  let client = Client::new("API_KEY").await.unwrap();
+    /// let mut blocks = client
+    ///     .query_as::<Block>("SELECT number, hash, miner FROM eth.recent_blocks LIMIT 10;")
+    ///     .await
+    ///     .unwrap();
+    /// while let Some(block) = blocks.next().await {
+    ///     let block = block.unwrap();
+    /// }
+    /// # }
+    /// ```
+    pub async fn query_as<T: DeserializeOwned + Send + 'static>(
+        &self,
+        query: &str,
+    ) -> Result<BoxStream<'static, Result<T>>> {
+        Ok(self.query(query).await?.into_rows())
+    }
+
+    /// Queries the Spice Flight endpoint and deserializes every result row
+    /// into a `T`, as with [`query_as`](Self::query_as).
+    pub async fn query_as_vec<T: DeserializeOwned + Send + 'static>(
+        &self,
+        query: &str,
+    ) -> Result<Vec<T>> {
+        self.query_as(query).await?.try_collect().await
+    }
+
+    /// Queries the Spice Flight endpoint and streams the results into the
+    /// file at `path`, which is created or truncated. Returns the number of
+    /// rows written. The file is written on a blocking thread, so large
+    /// exports do not stall the runtime.
+    /// ```no_run
+    /// # use spiceai::{Client, ExportFormat};
+    /// #
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// #  let client = Client::new("API_KEY").await.unwrap();
+    /// let rows = client
+    ///     .query_to_file(
+    ///         "SELECT * FROM eth.recent_blocks LIMIT 10;",
+    ///         "blocks.arrow",
+    ///         ExportFormat::ArrowFile,
+    ///     )
+    ///     .await;
+    /// # }
+    /// ```
+    pub async fn query_to_file(
+        &self,
+        query: &str,
+        path: impl AsRef<Path>,
+        format: ExportFormat,
+    ) -> Result<usize> {
+        let result = self.query(query).await?;
+        let file = File::create(path).map_err(Error::export)?;
+        result
+            .write_to_in_background(format, BufWriter::new(file))
+            .await
+    }
+
+    /// Returns the schema of the results of `query` on the Spice Flight
+    /// endpoint, without fetching any data.
+    /// ```no_run
+    /// # use spiceai::Client;
+    /// #
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// #  let client = Client::new("API_KEY").await.unwrap();
+    /// let schema = client.query_schema("SELECT * FROM eth.recent_blocks LIMIT 10;").await;
+    /// # }
+    /// ```
+    pub async fn query_schema(&self, query: &str) -> Result<SchemaRef> {
+        Ok(self.flight.query_info(query).await?.schema)
+    }
+
+    /// Describes the results of `query` on the Spice Flight endpoint (schema,
+    /// total records and bytes when known) without fetching any data.
+    pub async fn query_info(&self, query: &str) -> Result<QueryInfo> {
+        self.flight.query_info(query).await
+    }
+
+    /// Prepares `query` on the Spice Flight endpoint as a Flight SQL prepared
+    /// statement, so that parameters can be bound instead of formatted into
+    /// the SQL. Requires an endpoint that speaks Flight SQL.
+    /// ```no_run
+    /// # use spiceai::Client;
+    /// #
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// #  let client = Client::new("API_KEY").await.unwrap();
+    /// let mut statement = client
+    ///     .prepare("SELECT * FROM eth.recent_blocks WHERE number > $1 LIMIT 10;")
+    ///     .await
+    ///     .unwrap();
+    /// statement.bind_values([18_000_000_i64]).unwrap();
+    /// let data = statement.execute().await;
+    /// # }
+    /// ```
+    pub async fn prepare(&self, query: &str) -> Result<PreparedStatement> {
+        PreparedStatement::create(self.flight.clone(), query).await
+    }
+
+    /// Lists the catalogs available on the Spice Flight endpoint.
+    /// Requires an endpoint that speaks Flight SQL.
+    pub async fn list_catalogs(&self) -> Result<Vec<String>> {
+        catalog::list_catalogs(&self.flight).await
+    }
+
+    /// Lists the database schemas available on the Spice Flight endpoint,
+    /// optionally restricted to `catalog` and to names matching the SQL `LIKE`
+    /// `schema_pattern`. Requires an endpoint that speaks Flight SQL.
+    pub async fn list_schemas(
+        &self,
+        catalog: Option<&str>,
+        schema_pattern: Option<&str>,
+    ) -> Result<Vec<DatabaseSchema>> {
+        catalog::list_schemas(&self.flight, catalog, schema_pattern).await
+    }
+
+    /// Lists the tables available on the Spice Flight endpoint that match
+    /// `filter`. Requires an endpoint that speaks Flight SQL.
+    /// ```no_run
+    /// # use spiceai::{Client, TableFilter};
+    /// #
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// #  let client = Client::local().await.unwrap();
+    /// let tables = client
+    ///     .list_tables(&TableFilter::new().with_schema_pattern("eth"))
+    ///     .await;
+    /// # }
+    /// ```
+    pub async fn list_tables(&self, filter: &TableFilter) -> Result<Vec<TableInfo>> {
+        catalog::list_tables(&self.flight, filter).await
+    }
+
+    /// Returns the Arrow schema of the table `name`, given as `table`,
+    /// `schema.table` or `catalog.schema.table`. Requires an endpoint that
+    /// speaks Flight SQL.
+    /// ```no_run
+    /// # use spiceai::Client;
+    /// #
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// #  let client = Client::local().await.unwrap();
+    /// let schema = client.table_schema("eth.recent_blocks").await;
+    /// # }
+    /// ```
+    pub async fn table_schema(&self, name: &str) -> Result<SchemaRef> {
+        catalog::table_schema(&self.flight, name).await
+    }
+
     /// Queries the Spice Firecache endpoint with the given SQL query.
     /// ```
     /// # use spiceai::Client;
     /// #
     /// #  #[tokio::main]
     /// # async fn main() {
-    /// #  let mut client = Client::new("API_KEY").await.unwrap();
+    /// #  let client = Client::new("API_KEY").await.unwrap();
     /// let data = client.fire_query("SELECT * FROM eth.recent_blocks LIMIT 10;").await;
     /// # }
     /// ````
-    pub async fn fire_query(
-        &mut self,
-        query: &str,
-    ) -> Result<FlightRecordBatchStream, Box<dyn Error>> {
+    pub async fn fire_query(&self, query: &str) -> Result<QueryResult> {
         self.firecache.query(query).await
     }
 
+    /// Returns how often the client performed the Flight handshake, summed
+    /// over the Flight and Firecache endpoints. The bearer token is cached, so
+    /// handshakes only happen on first use and when the server rejects it.
+    #[must_use]
+    pub fn auth_metrics(&self) -> AuthMetrics {
+        self.flight.auth_metrics() + self.firecache.auth_metrics()
+    }
+
+    /// Queries the Spice Firecache endpoint with the given SQL query, bounded
+    /// by the timeout and cancellation token of `options`.
+    pub async fn fire_query_with_options(
+        &self,
+        query: &str,
+        options: &QueryOptions,
+    ) -> Result<QueryResult> {
+        self.firecache.query_with_options(query, options).await
+    }
+
     /// Get the supported pairs:
     /// ```rust
     /// # use spiceai::Client;
     /// #
     /// # #[tokio::main]
     /// # async fn main() {
-    /// #  let mut client = Client::new("API_KEY").await.unwrap();
+    /// #  let client = Client::new("API_KEY").await.unwrap();
     /// let supported_pairs = client.get_supported_pairs().await;
     /// # }
     /// ```
-    pub async fn get_supported_pairs(&self) -> Result<Vec<String>, Box<dyn Error>> {
+    pub async fn get_supported_pairs(&self) -> Result<Vec<TradingPair>> {
         self.prices.get_supported_pairs().await
     }
 
+    /// Gets the supported pairs, bounded by the timeout and cancellation token
+    /// of `options`.
+    pub async fn get_supported_pairs_with_options(
+        &self,
+        options: &QueryOptions,
+    ) -> Result<Vec<TradingPair>> {
+        self.prices.get_supported_pairs_with_options(options).await
+    }
+
+    /// Checks that every pair is supported, failing with
+    /// [`Error::InvalidArgument`] naming the unsupported ones:
+    /// ```no_run
+    /// # use spiceai::{Client, TradingPair};
+    /// #
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// #  let client = Client::new("API_KEY").await.unwrap();
+    /// let pair: TradingPair = "BTC-USDC".parse().unwrap();
+    /// client.validate_pairs(&[pair]).await.unwrap();
+    /// # }
+    /// ```
+    pub async fn validate_pairs(&self, pairs: &[TradingPair]) -> Result<()> {
+        self.prices.validate_pairs(pairs).await
+    }
+
     /// Get the latest price for a token pair:
     /// ```rust
     /// # use spiceai::Client;
     /// #
     /// # #[tokio::main]
     /// # async fn main() {
-    /// #  let mut client = Client::new("API_KEY").await.unwrap();
-    /// let price_data = client.get_prices(&["BTC-USDC"]).await;
+    /// #  let client = Client::new("API_KEY").await.unwrap();
+    /// let price_data = client.get_prices(&["BTC-USDC".parse().unwrap()]).await;
     /// # }
     /// ```
-    pub async fn get_prices(&self, pairs: &[&str]) -> Result<LatestPricesResponse, Box<dyn Error>> {
+    pub async fn get_prices(&self, pairs: &[TradingPair]) -> Result<LatestPricesResponse> {
         self.prices.get_prices(pairs).await
     }
 
-    /// Get historical data:
+    /// Gets the latest prices of `pairs`, bounded by the timeout and
+    /// cancellation token of `options`.
+    pub async fn get_prices_with_options(
+        &self,
+        pairs: &[TradingPair],
+        options: &QueryOptions,
+    ) -> Result<LatestPricesResponse> {
+        self.prices.get_prices_with_options(pairs, options).await
+    }
+
+    /// Get historical data. The request is checked before it is sent: `start`
+    /// must be before `end` and, when
+    /// [`SpiceClientBuilder::max_historical_intervals`] is set, the range may
+    /// not span more intervals than it allows.
     /// ```rust
-    /// # use spiceai::Client;
+    /// # use spiceai::{Client, Granularity};
     /// # use chrono::Utc;
     /// # use chrono::Duration;
     /// # use std::ops::Sub;
     /// #
     /// # #[tokio::main]
     /// # async fn main() {
-    /// #  let mut client = Client::new("API_KEY").await.unwrap();
+    /// #  let client = Client::new("API_KEY").await.unwrap();
     /// #  let now = Utc::now();
     /// #  let start = now.sub(Duration::seconds(3600));
     /// let historical_price_data = client
-    ///     .get_historical_prices(&["BTC-USDC"], Some(start), Some(now), Option::None).await;
+    ///     .get_historical_prices(&["BTC-USDC".parse().unwrap()], Some(start), Some(now), Some(Granularity::Minute))
+    ///     .await;
     /// # }
     /// ```
     pub async fn get_historical_prices(
         &self,
-        pairs: &[&str],
+        pairs: &[TradingPair],
         start: Option<DateTime<Utc>>,
         end: Option<DateTime<Utc>>,
-        granularity: Option<&str>,
-    ) -> Result<HashMap<String, Vec<HistoricalPriceData>>, Box<dyn Error>> {
+        granularity: Option<Granularity>,
+    ) -> Result<HashMap<TradingPair, Vec<HistoricalPriceData>>> {
         self.prices
             .get_historical_prices(pairs, start, end, granularity)
             .await
     }
+
+    /// Gets historical data like [`get_historical_prices`](Self::get_historical_prices),
+    /// bounded by the timeout and cancellation token of `options`.
+    pub async fn get_historical_prices_with_options(
+        &self,
+        pairs: &[TradingPair],
+        start: Option<DateTime<Utc>>,
+        end: Option<DateTime<Utc>>,
+        granularity: Option<Granularity>,
+        options: &QueryOptions,
+    ) -> Result<HashMap<TradingPair, Vec<HistoricalPriceData>>> {
+        self.prices
+            .get_historical_prices_with_options(pairs, start, end, granularity, options)
+            .await
+    }
+
+    /// Streams historical prices over `[start, end)`, however long the range.
+    /// It is fetched in windows of
+    /// [`SpiceClientBuilder::max_historical_intervals`] intervals, or 1,440 if
+    /// unset, several at a time, and prices are yielded as `(pair, price)` in
+    /// timestamp order.
+    /// ```no_run
+    /// # use spiceai::{Client, Granularity, StreamExt};
+    /// # use chrono::{Duration, Utc};
+    /// #
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// #  let client = Client::new("API_KEY").await.unwrap();
+    /// let end = Utc::now();
+    /// let pairs = ["BTC-USD".parse().unwrap()];
+    /// let mut prices = client
+    ///     .stream_historical_prices(&pairs, end - Duration::days(90), end, Granularity::Minute)
+    ///     .unwrap();
+    /// while let Some(price) = prices.next().await {
+    ///     let (pair, price) = price.unwrap();
+    ///     println!("{pair}: {price}");
+    /// }
+    /// # }
+    /// ```
+    pub fn stream_historical_prices(
+        &self,
+        pairs: &[TradingPair],
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+        granularity: Granularity,
+    ) -> Result<BoxStream<'static, Result<(TradingPair, HistoricalPriceData)>>> {
+        self.prices
+            .stream_historical_prices(pairs, start, end, granularity)
+    }
+
+    /// Streams historical prices like
+    /// [`stream_historical_prices`](Self::stream_historical_prices). The
+    /// stream ends with [`Error::Timeout`] or [`Error::Cancelled`] once the
+    /// timeout of `options` passes or its token is cancelled.
+    pub fn stream_historical_prices_with_options(
+        &self,
+        pairs: &[TradingPair],
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+        granularity: Granularity,
+        options: &QueryOptions,
+    ) -> Result<BoxStream<'static, Result<(TradingPair, HistoricalPriceData)>>> {
+        self.prices
+            .stream_historical_prices_with_options(pairs, start, end, granularity, options)
+    }
+
+    /// Subscribes to the latest prices of `pairs`, polled every `interval`.
+    /// A [`PriceTick`] is yielded whenever the prices of a pair change, and
+    /// rate limited polls are backed off internally. Polling stops when the
+    /// stream is dropped.
+    /// ```no_run
+    /// # use spiceai::{Client, StreamExt};
+    /// # use std::time::Duration;
+    /// #
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// #  let client = Client::new("API_KEY").await.unwrap();
+    /// let pairs = ["BTC-USD".parse().unwrap(), "ETH-USD".parse().unwrap()];
+    /// let mut ticks = client
+    ///     .subscribe_prices(&pairs, Duration::from_secs(5))
+    ///     .unwrap();
+    /// while let Some(tick) = ticks.next().await {
+    ///     println!("{}", tick.unwrap());
+    /// }
+    /// # }
+    /// ```
+    pub fn subscribe_prices(
+        &self,
+        pairs: &[TradingPair],
+        interval: Duration,
+    ) -> Result<BoxStream<'static, Result<PriceTick>>> {
+        self.prices.subscribe_prices(pairs, interval)
+    }
+
+    /// Subscribes to the latest prices of `pairs` like
+    /// [`subscribe_prices`](Self::subscribe_prices). The timeout of `options`
+    /// bounds each poll, and cancelling its token ends the stream with
+    /// [`Error::Cancelled`].
+    pub fn subscribe_prices_with_options(
+        &self,
+        pairs: &[TradingPair],
+        interval: Duration,
+        options: &QueryOptions,
+    ) -> Result<BoxStream<'static, Result<PriceTick>>> {
+        self.prices
+            .subscribe_prices_with_options(pairs, interval, options)
+    }
 }
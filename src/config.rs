@@ -1,3 +1,5 @@
 pub const HTTPS_ADDR: &str = "https://data.spiceai.io";
 pub const FLIGHT_ADDR: &str = "https://flight.spiceai.io";
 pub const FIRECACHE_ADDR: &str = "https://firecache.spiceai.io";
+pub const LOCAL_FLIGHT_ADDR: &str = "http://localhost:50051";
+pub const LOCAL_HTTP_ADDR: &str = "http://localhost:8090";
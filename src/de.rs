@@ -0,0 +1,483 @@
+//! Deserialization of query result rows into serde types.
+
+use std::fmt;
+
+use arrow::array::{Array, ArrayRef, AsArray};
+use arrow::compute::cast;
+use arrow::datatypes::{
+    DataType, Decimal128Type, Float16Type, Float32Type, Float64Type, Int16Type, Int32Type,
+    Int64Type, Int8Type, TimeUnit, TimestampMicrosecondType, TimestampMillisecondType,
+    TimestampNanosecondType, TimestampSecondType, UInt16Type, UInt32Type, UInt64Type, UInt8Type,
+};
+use arrow::record_batch::RecordBatch;
+use arrow::temporal_conversions::{
+    timestamp_ms_to_datetime, timestamp_ns_to_datetime, timestamp_s_to_datetime,
+    timestamp_us_to_datetime,
+};
+use arrow::util::display::{ArrayFormatter, FormatOptions};
+use chrono::{SecondsFormat, TimeZone, Utc};
+use serde::de::{
+    self, DeserializeOwned, DeserializeSeed, IntoDeserializer, MapAccess, SeqAccess, Visitor,
+};
+use serde::forward_to_deserialize_any;
+
+use crate::error::{Error, Result};
+
+/// Deserializes every row of `batch` into a `T`.
+///
+/// Struct fields are matched to columns by name and tuples to columns by
+/// position; a single-column result can also be read into a plain value.
+/// Nullable columns map to `Option` fields, timestamps to
+/// `chrono::DateTime<Utc>` (or their raw integer value), decimals to `f64`,
+/// to `i128` when they have no fractional digits, or to their string form.
+/// ```
+/// use arrow::array::{Int64Array, StringArray};
+/// use arrow::datatypes::{DataType, Field, Schema};
+/// use arrow::record_batch::RecordBatch;
+/// use serde::Deserialize;
+/// use std::sync::Arc;
+///
+/// #[derive(Deserialize)]
+/// struct Block {
+///     number: i64,
+///     miner: Option<String>,
+/// }
+///
+/// let schema = Schema::new(vec![
+///     Field::new("number", DataType::Int64, false),
+///     Field::new("miner", DataType::Utf8, true),
+/// ]);
+/// let batch = RecordBatch::try_new(
+///     Arc::new(schema),
+///     vec![
+///         Arc::new(Int64Array::from(vec![1, 2])),
+///         Arc::new(StringArray::from(vec![Some("0xabc"), None])),
+///     ],
+/// )
+/// .unwrap();
+/// let blocks: Vec<Block> = spiceai::from_record_batch(&batch).unwrap();
+/// assert_eq!(blocks[1].miner, None);
+/// ```
+pub fn from_record_batch<T: DeserializeOwned>(batch: &RecordBatch) -> Result<Vec<T>> {
+    // Dictionary-encoded columns are decoded once rather than per value.
+    let columns = batch
+        .columns()
+        .iter()
+        .map(|column| match column.data_type() {
+            DataType::Dictionary(_, value_type) => cast(column, value_type),
+            _ => Ok(column.clone()),
+        })
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+    let schema = batch.schema();
+    let names: Vec<&str> = schema.fields().iter().map(|f| f.name().as_str()).collect();
+
+    (0..batch.num_rows())
+        .map(|row| {
+            T::deserialize(RowDeserializer {
+                names: &names,
+                columns: &columns,
+                row,
+            })
+            .map_err(Error::from)
+        })
+        .collect()
+}
+
+#[derive(Debug)]
+pub(crate) struct DeError {
+    column: Option<String>,
+    message: String,
+}
+
+impl DeError {
+    fn in_column(mut self, column: &str) -> Self {
+        self.column.get_or_insert_with(|| column.to_string());
+        self
+    }
+}
+
+impl fmt::Display for DeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for DeError {}
+
+impl de::Error for DeError {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        DeError {
+            column: None,
+            message: msg.to_string(),
+        }
+    }
+}
+
+impl From<DeError> for Error {
+    fn from(err: DeError) -> Self {
+        Error::Deserialize {
+            column: err.column,
+            message: err.message,
+        }
+    }
+}
+
+/// Deserializes one row as a map of column names to values, or a sequence of
+/// values.
+struct RowDeserializer<'a> {
+    names: &'a [&'a str],
+    columns: &'a [ArrayRef],
+    row: usize,
+}
+
+impl<'a> RowDeserializer<'a> {
+    fn columns(self) -> Columns<'a> {
+        Columns {
+            row: self,
+            index: 0,
+        }
+    }
+
+    fn single_column(self) -> std::result::Result<ValueDeserializer<'a>, DeError> {
+        match self.columns {
+            [column] => Ok(ValueDeserializer {
+                array: column,
+                row: self.row,
+            }),
+            _ => Err(de::Error::custom(format!(
+                "expected a single column, got {}",
+                self.columns.len()
+            ))),
+        }
+    }
+}
+
+macro_rules! deserialize_single_column {
+    ($($method:ident)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> std::result::Result<V::Value, DeError> {
+                let name = self.names.first().copied().unwrap_or_default();
+                self.single_column()?
+                    .$method(visitor)
+                    .map_err(|e| e.in_column(name))
+            }
+        )*
+    };
+}
+
+impl<'de, 'a> de::Deserializer<'de> for RowDeserializer<'a> {
+    type Error = DeError;
+
+    fn deserialize_any<V: Visitor<'de>>(
+        self,
+        visitor: V,
+    ) -> std::result::Result<V::Value, DeError> {
+        visitor.visit_map(self.columns())
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(
+        self,
+        visitor: V,
+    ) -> std::result::Result<V::Value, DeError> {
+        visitor.visit_seq(self.columns())
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(
+        self,
+        _len: usize,
+        visitor: V,
+    ) -> std::result::Result<V::Value, DeError> {
+        visitor.visit_seq(self.columns())
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _len: usize,
+        visitor: V,
+    ) -> std::result::Result<V::Value, DeError> {
+        visitor.visit_seq(self.columns())
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> std::result::Result<V::Value, DeError> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    deserialize_single_column! {
+        deserialize_bool deserialize_i8 deserialize_i16 deserialize_i32 deserialize_i64
+        deserialize_i128 deserialize_u8 deserialize_u16 deserialize_u32 deserialize_u64
+        deserialize_u128 deserialize_f32 deserialize_f64 deserialize_char deserialize_str
+        deserialize_string deserialize_bytes deserialize_byte_buf deserialize_option
+    }
+
+    forward_to_deserialize_any! {
+        unit unit_struct map struct enum identifier ignored_any
+    }
+}
+
+struct Columns<'a> {
+    row: RowDeserializer<'a>,
+    index: usize,
+}
+
+impl<'a> Columns<'a> {
+    fn next_value<'de, T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> std::result::Result<T::Value, DeError> {
+        let name = self.row.names[self.index];
+        let array = &self.row.columns[self.index];
+        let row = self.row.row;
+        self.index += 1;
+        seed.deserialize(ValueDeserializer { array, row })
+            .map_err(|e| {
+                if array.is_null(row) {
+                    de::Error::custom("unexpected null, use an `Option` to accept nulls")
+                } else {
+                    e
+                }
+            })
+            .map_err(|e| e.in_column(name))
+    }
+}
+
+impl<'de, 'a> MapAccess<'de> for Columns<'a> {
+    type Error = DeError;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> std::result::Result<Option<K::Value>, DeError> {
+        match self.row.names.get(self.index) {
+            Some(name) => seed.deserialize((*name).into_deserializer()).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(
+        &mut self,
+        seed: V,
+    ) -> std::result::Result<V::Value, DeError> {
+        self.next_value(seed)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.row.columns.len() - self.index)
+    }
+}
+
+impl<'de, 'a> SeqAccess<'de> for Columns<'a> {
+    type Error = DeError;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> std::result::Result<Option<T::Value>, DeError> {
+        if self.index == self.row.columns.len() {
+            return Ok(None);
+        }
+        self.next_value(seed).map(Some)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.row.columns.len() - self.index)
+    }
+}
+
+/// Deserializes the value at `row` of `array`.
+struct ValueDeserializer<'a> {
+    array: &'a ArrayRef,
+    row: usize,
+}
+
+impl ValueDeserializer<'_> {
+    fn timestamp(&self) -> Option<(i64, TimeUnit)> {
+        let DataType::Timestamp(unit, _) = self.array.data_type() else {
+            return None;
+        };
+        let value = match unit {
+            TimeUnit::Second => self
+                .array
+                .as_primitive::<TimestampSecondType>()
+                .value(self.row),
+            TimeUnit::Millisecond => self
+                .array
+                .as_primitive::<TimestampMillisecondType>()
+                .value(self.row),
+            TimeUnit::Microsecond => self
+                .array
+                .as_primitive::<TimestampMicrosecondType>()
+                .value(self.row),
+            TimeUnit::Nanosecond => self
+                .array
+                .as_primitive::<TimestampNanosecondType>()
+                .value(self.row),
+        };
+        Some((value, unit.clone()))
+    }
+
+    fn decimal_f64(&self) -> Option<f64> {
+        let DataType::Decimal128(_, scale) = self.array.data_type() else {
+            return None;
+        };
+        let value = self.array.as_primitive::<Decimal128Type>().value(self.row);
+        #[allow(clippy::cast_precision_loss)]
+        Some(value as f64 / 10_f64.powi(i32::from(*scale)))
+    }
+
+    fn formatted(&self) -> std::result::Result<String, DeError> {
+        let formatter = ArrayFormatter::try_new(self.array.as_ref(), &FormatOptions::default())
+            .map_err(de::Error::custom)?;
+        Ok(formatter.value(self.row).to_string())
+    }
+}
+
+macro_rules! primitive_value {
+    ($self:ident, $type:ty) => {
+        $self.array.as_primitive::<$type>().value($self.row)
+    };
+}
+
+macro_rules! deserialize_integer {
+    ($($method:ident => $visit:ident),*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> std::result::Result<V::Value, DeError> {
+                match self.timestamp() {
+                    Some((value, _)) if !self.array.is_null(self.row) => visitor.$visit(value.into()),
+                    _ => self.deserialize_any(visitor),
+                }
+            }
+        )*
+    };
+}
+
+impl<'de, 'a> de::Deserializer<'de> for ValueDeserializer<'a> {
+    type Error = DeError;
+
+    fn deserialize_any<V: Visitor<'de>>(
+        self,
+        visitor: V,
+    ) -> std::result::Result<V::Value, DeError> {
+        if self.array.is_null(self.row) {
+            return visitor.visit_none();
+        }
+        match self.array.data_type() {
+            DataType::Null => visitor.visit_none(),
+            DataType::Boolean => visitor.visit_bool(self.array.as_boolean().value(self.row)),
+            DataType::Int8 => visitor.visit_i8(primitive_value!(self, Int8Type)),
+            DataType::Int16 => visitor.visit_i16(primitive_value!(self, Int16Type)),
+            DataType::Int32 => visitor.visit_i32(primitive_value!(self, Int32Type)),
+            DataType::Int64 => visitor.visit_i64(primitive_value!(self, Int64Type)),
+            DataType::UInt8 => visitor.visit_u8(primitive_value!(self, UInt8Type)),
+            DataType::UInt16 => visitor.visit_u16(primitive_value!(self, UInt16Type)),
+            DataType::UInt32 => visitor.visit_u32(primitive_value!(self, UInt32Type)),
+            DataType::UInt64 => visitor.visit_u64(primitive_value!(self, UInt64Type)),
+            DataType::Float16 => visitor.visit_f32(primitive_value!(self, Float16Type).to_f32()),
+            DataType::Float32 => visitor.visit_f32(primitive_value!(self, Float32Type)),
+            DataType::Float64 => visitor.visit_f64(primitive_value!(self, Float64Type)),
+            DataType::Utf8 => visitor.visit_str(self.array.as_string::<i32>().value(self.row)),
+            DataType::LargeUtf8 => visitor.visit_str(self.array.as_string::<i64>().value(self.row)),
+            DataType::Binary => visitor.visit_bytes(self.array.as_binary::<i32>().value(self.row)),
+            DataType::LargeBinary => {
+                visitor.visit_bytes(self.array.as_binary::<i64>().value(self.row))
+            }
+            DataType::FixedSizeBinary(_) => {
+                visitor.visit_bytes(self.array.as_fixed_size_binary().value(self.row))
+            }
+            DataType::Timestamp(..) => {
+                let Some((value, unit)) = self.timestamp() else {
+                    unreachable!("timestamp column")
+                };
+                let datetime = match unit {
+                    TimeUnit::Second => timestamp_s_to_datetime(value),
+                    TimeUnit::Millisecond => timestamp_ms_to_datetime(value),
+                    TimeUnit::Microsecond => timestamp_us_to_datetime(value),
+                    TimeUnit::Nanosecond => timestamp_ns_to_datetime(value),
+                }
+                .ok_or_else(|| de::Error::custom(format!("timestamp {value} is out of range")))?;
+                visitor.visit_string(
+                    Utc.from_utc_datetime(&datetime)
+                        .to_rfc3339_opts(SecondsFormat::AutoSi, true),
+                )
+            }
+            DataType::Decimal128(_, 0) => {
+                visitor.visit_i128(primitive_value!(self, Decimal128Type))
+            }
+            _ => visitor.visit_string(self.formatted()?),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(
+        self,
+        visitor: V,
+    ) -> std::result::Result<V::Value, DeError> {
+        if self.array.is_null(self.row) {
+            visitor.visit_none()
+        } else {
+            visitor.visit_some(self)
+        }
+    }
+
+    deserialize_integer! {
+        deserialize_i64 => visit_i64,
+        deserialize_i128 => visit_i128
+    }
+
+    fn deserialize_f32<V: Visitor<'de>>(
+        self,
+        visitor: V,
+    ) -> std::result::Result<V::Value, DeError> {
+        self.deserialize_f64(visitor)
+    }
+
+    fn deserialize_f64<V: Visitor<'de>>(
+        self,
+        visitor: V,
+    ) -> std::result::Result<V::Value, DeError> {
+        match self.decimal_f64() {
+            Some(value) if !self.array.is_null(self.row) => visitor.visit_f64(value),
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> std::result::Result<V::Value, DeError> {
+        match self.array.data_type() {
+            DataType::Utf8 if !self.array.is_null(self.row) => visitor.visit_enum(
+                self.array
+                    .as_string::<i32>()
+                    .value(self.row)
+                    .into_deserializer(),
+            ),
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> std::result::Result<V::Value, DeError> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(
+        self,
+        visitor: V,
+    ) -> std::result::Result<V::Value, DeError> {
+        visitor.visit_unit()
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 u8 u16 u32 u64 u128 char str string bytes byte_buf unit
+        unit_struct seq tuple tuple_struct map struct identifier
+    }
+}
@@ -0,0 +1,216 @@
+use std::fmt;
+use std::time::Duration;
+
+use arrow::error::ArrowError;
+use arrow_flight::error::FlightError;
+
+pub(crate) type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// A specialized `Result` type for Spice client operations.
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// Errors returned by the Spice client.
+///
+/// Variants carry the underlying `tonic`/`reqwest` error as their
+/// [`source`](std::error::Error::source) where one exists, so callers can
+/// match on the failure kind while still logging the full chain.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// The API key is not in the expected `<app_id>|<key>` format.
+    InvalidApiKey,
+
+    /// The server rejected the credentials.
+    Auth {
+        message: String,
+        source: Option<Box<tonic::Status>>,
+    },
+
+    /// The server is throttling requests. `retry_after` is set when the server
+    /// advertised how long to wait.
+    RateLimited { retry_after: Option<Duration> },
+
+    /// The server rejected the request as malformed.
+    BadRequest { message: String },
+
+    /// The HTTP API answered with an unexpected status.
+    Http {
+        status: reqwest::StatusCode,
+        message: String,
+    },
+
+    /// A Flight call failed with the given gRPC status code.
+    Flight {
+        code: tonic::Code,
+        source: Box<tonic::Status>,
+    },
+
+    /// A Flight channel could not be established.
+    Transport(tonic::transport::Error),
+
+    /// An HTTP request could not be sent or its response not read.
+    Request(reqwest::Error),
+
+    /// A response could not be decoded.
+    Decode(BoxError),
+
+    /// A result row could not be deserialized into the requested type.
+    /// `column` names the offending column when the failure is specific to one.
+    Deserialize {
+        column: Option<String>,
+        message: String,
+    },
+
+    /// Results could not be written to an export destination.
+    Export(BoxError),
+
+    /// An argument was rejected before any request was sent.
+    InvalidArgument { message: String },
+
+    /// The server did not return any endpoint to fetch results from.
+    NoEndpoints,
+
+    /// The operation did not complete before its deadline.
+    Timeout,
+
+    /// The operation was cancelled through its cancellation token.
+    Cancelled,
+
+    /// The client configuration is invalid.
+    Config {
+        message: String,
+        source: Option<BoxError>,
+    },
+
+    /// A replaying [`Cassette`](crate::testing::Cassette) holds no response
+    /// for a request.
+    #[cfg(feature = "test-util")]
+    Cassette(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::InvalidApiKey => write!(f, "Invalid API key format"),
+            Error::Auth { message, .. } => write!(f, "Authentication failed: {message}"),
+            Error::RateLimited { .. } => write!(f, "Rate limit exceeded, slow down"),
+            Error::BadRequest { message } => write!(f, "Bad request: {message}"),
+            Error::Http { status, message } => {
+                write!(f, "Unexpected response status {status}: {message}")
+            }
+            Error::Flight { code, source } => {
+                write!(
+                    f,
+                    "Flight request failed with {code:?}: {}",
+                    source.message()
+                )
+            }
+            Error::Transport(e) => write!(f, "Transport error: {e}"),
+            Error::Request(e) => write!(f, "HTTP request failed: {e}"),
+            Error::Decode(e) => write!(f, "Failed to decode response: {e}"),
+            Error::Deserialize {
+                column: Some(column),
+                message,
+            } => write!(f, "Failed to deserialize column \"{column}\": {message}"),
+            Error::Deserialize {
+                column: None,
+                message,
+            } => write!(f, "Failed to deserialize row: {message}"),
+            Error::Export(e) => write!(f, "Failed to export results: {e}"),
+            Error::InvalidArgument { message } => write!(f, "Invalid argument: {message}"),
+            Error::NoEndpoints => write!(f, "No endpoints found"),
+            Error::Timeout => write!(f, "Operation timed out"),
+            Error::Cancelled => write!(f, "Operation cancelled"),
+            Error::Config { message, .. } => write!(f, "Invalid configuration: {message}"),
+            #[cfg(feature = "test-util")]
+            Error::Cassette(message) => write!(f, "Cassette mismatch: {message}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Auth {
+                source: Some(status),
+                ..
+            }
+            | Error::Flight { source: status, .. } => Some(status.as_ref()),
+            Error::Transport(e) => Some(e),
+            Error::Request(e) => Some(e),
+            Error::Decode(e)
+            | Error::Export(e)
+            | Error::Config {
+                source: Some(e), ..
+            } => Some(e.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl From<tonic::transport::Error> for Error {
+    fn from(err: tonic::transport::Error) -> Self {
+        Error::Transport(err)
+    }
+}
+
+impl Error {
+    pub(crate) fn export(source: impl Into<BoxError>) -> Self {
+        Error::Export(source.into())
+    }
+
+    pub(crate) fn config_with_source(
+        message: impl Into<String>,
+        source: impl Into<BoxError>,
+    ) -> Self {
+        Error::Config {
+            message: message.into(),
+            source: Some(source.into()),
+        }
+    }
+
+    pub(crate) fn decode(message: impl Into<String>) -> Self {
+        Error::Decode(message.into().into())
+    }
+}
+
+impl From<tonic::Status> for Error {
+    fn from(status: tonic::Status) -> Self {
+        match status.code() {
+            tonic::Code::Unauthenticated => Error::Auth {
+                message: status.message().to_string(),
+                source: Some(Box::new(status)),
+            },
+            code => Error::Flight {
+                code,
+                source: Box::new(status),
+            },
+        }
+    }
+}
+
+impl From<FlightError> for Error {
+    fn from(err: FlightError) -> Self {
+        match err {
+            FlightError::Tonic(status) => status.into(),
+            FlightError::Arrow(err) => err.into(),
+            err => Error::Decode(Box::new(err)),
+        }
+    }
+}
+
+impl From<ArrowError> for Error {
+    fn from(err: ArrowError) -> Self {
+        Error::Decode(Box::new(err))
+    }
+}
+
+impl From<reqwest::Error> for Error {
+    fn from(err: reqwest::Error) -> Self {
+        if err.is_decode() {
+            Error::Decode(Box::new(err))
+        } else {
+            Error::Request(err)
+        }
+    }
+}
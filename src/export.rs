@@ -0,0 +1,155 @@
+use std::fmt;
+use std::io::Write;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use arrow::datatypes::Schema;
+use arrow::ipc::writer::{FileWriter, StreamWriter};
+use arrow::record_batch::RecordBatch;
+#[cfg(feature = "parquet")]
+use parquet::arrow::ArrowWriter;
+#[cfg(feature = "parquet")]
+pub use parquet::basic::Compression as ParquetCompression;
+#[cfg(feature = "parquet")]
+use parquet::file::properties::WriterProperties;
+
+use crate::error::{Error, Result};
+
+/// File formats query results can be exported to with
+/// [`QueryResult::write_to`](crate::QueryResult::write_to).
+///
+/// Formats can also be parsed from their usual file extension, e.g.
+/// `"csv".parse::<ExportFormat>()`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[non_exhaustive]
+pub enum ExportFormat {
+    /// Comma-separated values with a header row.
+    Csv,
+    /// Newline-delimited JSON, one object per row.
+    NdJson,
+    /// The Arrow IPC file format, also known as Feather v2.
+    ArrowFile,
+    /// The Arrow IPC streaming format.
+    ArrowStream,
+    /// Apache Parquet, with the given compression codec.
+    #[cfg(feature = "parquet")]
+    Parquet { compression: ParquetCompression },
+}
+
+impl fmt::Display for ExportFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExportFormat::Csv => write!(f, "csv"),
+            ExportFormat::NdJson => write!(f, "ndjson"),
+            ExportFormat::ArrowFile => write!(f, "arrow"),
+            ExportFormat::ArrowStream => write!(f, "arrows"),
+            #[cfg(feature = "parquet")]
+            ExportFormat::Parquet { .. } => write!(f, "parquet"),
+        }
+    }
+}
+
+impl FromStr for ExportFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "csv" => Ok(ExportFormat::Csv),
+            "json" | "ndjson" | "jsonl" => Ok(ExportFormat::NdJson),
+            "arrow" | "feather" | "ipc" => Ok(ExportFormat::ArrowFile),
+            "arrows" | "stream" => Ok(ExportFormat::ArrowStream),
+            #[cfg(feature = "parquet")]
+            "parquet" => Ok(ExportFormat::Parquet {
+                compression: ParquetCompression::SNAPPY,
+            }),
+            _ => Err(Error::InvalidArgument {
+                message: format!("Unsupported export format \"{s}\""),
+            }),
+        }
+    }
+}
+
+/// Writes `batches` to `writer` in `format` and returns the number of rows
+/// written, like [`QueryResult::write_to`](crate::QueryResult::write_to)
+/// does for query results. The schema is taken from the first batch.
+pub fn write_batches<W: Write + Send>(
+    batches: &[RecordBatch],
+    format: ExportFormat,
+    writer: W,
+) -> Result<usize> {
+    let schema = batches
+        .first()
+        .map_or_else(|| Arc::new(Schema::empty()), RecordBatch::schema);
+    let mut out = BatchWriter::try_new(format, writer, &schema)?;
+    let mut rows = 0;
+    for batch in batches {
+        rows += batch.num_rows();
+        out.write(batch)?;
+    }
+    out.finish()?;
+    Ok(rows)
+}
+
+/// Writes batches in an [`ExportFormat`] as they arrive.
+pub(crate) enum BatchWriter<W: Write + Send> {
+    Csv(Box<arrow::csv::Writer<W>>),
+    NdJson(arrow::json::LineDelimitedWriter<W>),
+    ArrowFile(FileWriter<W>),
+    ArrowStream(StreamWriter<W>),
+    #[cfg(feature = "parquet")]
+    Parquet(ArrowWriter<W>),
+}
+
+impl<W: Write + Send> BatchWriter<W> {
+    pub(crate) fn try_new(format: ExportFormat, writer: W, schema: &Schema) -> Result<Self> {
+        Ok(match format {
+            ExportFormat::Csv => BatchWriter::Csv(Box::new(arrow::csv::Writer::new(writer))),
+            ExportFormat::NdJson => {
+                BatchWriter::NdJson(arrow::json::LineDelimitedWriter::new(writer))
+            }
+            ExportFormat::ArrowFile => {
+                BatchWriter::ArrowFile(FileWriter::try_new(writer, schema).map_err(Error::export)?)
+            }
+            ExportFormat::ArrowStream => BatchWriter::ArrowStream(
+                StreamWriter::try_new(writer, schema).map_err(Error::export)?,
+            ),
+            #[cfg(feature = "parquet")]
+            ExportFormat::Parquet { compression } => {
+                let props = WriterProperties::builder()
+                    .set_compression(compression)
+                    .build();
+                BatchWriter::Parquet(
+                    ArrowWriter::try_new(writer, schema.clone().into(), Some(props))
+                        .map_err(Error::export)?,
+                )
+            }
+        })
+    }
+
+    pub(crate) fn write(&mut self, batch: &RecordBatch) -> Result<()> {
+        match self {
+            BatchWriter::Csv(w) => w.write(batch).map_err(Error::export),
+            BatchWriter::NdJson(w) => w.write(batch).map_err(Error::export),
+            BatchWriter::ArrowFile(w) => w.write(batch).map_err(Error::export),
+            BatchWriter::ArrowStream(w) => w.write(batch).map_err(Error::export),
+            #[cfg(feature = "parquet")]
+            BatchWriter::Parquet(w) => w.write(batch).map_err(Error::export),
+        }
+    }
+
+    /// Writes any footer and flushes the underlying writer.
+    pub(crate) fn finish(self) -> Result<()> {
+        let mut writer = match self {
+            BatchWriter::Csv(w) => (*w).into_inner(),
+            BatchWriter::NdJson(mut w) => {
+                w.finish().map_err(Error::export)?;
+                w.into_inner()
+            }
+            BatchWriter::ArrowFile(w) => w.into_inner().map_err(Error::export)?,
+            BatchWriter::ArrowStream(w) => w.into_inner().map_err(Error::export)?,
+            #[cfg(feature = "parquet")]
+            BatchWriter::Parquet(w) => w.into_inner().map_err(Error::export)?,
+        };
+        writer.flush().map_err(Error::export)
+    }
+}
@@ -1,149 +1,784 @@
-use arrow::error::ArrowError;
+#[cfg(feature = "test-util")]
+use crate::cassette::Cassette;
+use crate::error::{Error, Result};
+use crate::options::{bounded, remaining, QueryOptions, QueryProtocol};
+use crate::result::{QueryResult, SharedTrailers};
+use crate::retry::RetryPolicy;
+use crate::tls::{new_tls_flight_channel, ChannelOptions};
+use arrow::datatypes::{Schema, SchemaRef};
+use arrow::record_batch::RecordBatch;
 use arrow_flight::decode::FlightRecordBatchStream;
 use arrow_flight::error::FlightError;
 use arrow_flight::flight_service_client::FlightServiceClient;
-use arrow_flight::FlightDescriptor;
+use arrow_flight::sql::{CommandStatementQuery, ProstMessageExt};
 use arrow_flight::HandshakeRequest;
 use arrow_flight::HandshakeResponse;
+use arrow_flight::{
+    Action, FlightData, FlightDescriptor, FlightEndpoint, FlightInfo, IpcMessage, PutResult,
+    SchemaResult,
+};
 use base64::prelude::BASE64_STANDARD;
 use base64::Engine;
 use bytes::Bytes;
-use futures::stream;
-use futures::TryStreamExt;
+use futures::stream::{self, BoxStream};
+use futures::{Stream, StreamExt, TryFutureExt, TryStreamExt};
+use prost::Message;
 use std::collections::HashMap;
-use std::error::Error;
+use std::future::Future;
+use std::pin::Pin;
 use std::str::FromStr;
-use tonic::metadata::AsciiMetadataKey;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+use std::task::{Context, Poll};
+use tokio::sync::Mutex;
+use tokio::time::{Instant, Sleep};
+use tokio_util::sync::WaitForCancellationFutureOwned;
+use tonic::metadata::{AsciiMetadataKey, MetadataMap};
 use tonic::transport::Channel;
-use tonic::IntoRequest;
+use tonic::{IntoRequest, Streaming};
 
+/// A stream of record batches covering every endpoint of a query.
+pub(crate) type RecordBatchStream = BoxStream<'static, Result<RecordBatch>>;
+
+/// Location URI meaning "fetch from the channel the `FlightInfo` came from".
+const REUSE_CONNECTION_URI: &str = "arrow-flight-reuse-connection://?";
+
+/// Describes the results of a query, as advertised by the server before they
+/// are fetched.
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueryInfo {
+    /// The schema of the result batches.
+    pub schema: SchemaRef,
+    /// The number of rows in the result, if known to the server.
+    pub total_records: Option<u64>,
+    /// The size of the result in bytes, if known to the server.
+    pub total_bytes: Option<u64>,
+    /// The number of endpoints the result is spread across.
+    pub endpoints: usize,
+    /// Whether batches must be read in endpoint order.
+    pub ordered: bool,
+}
+
+impl QueryInfo {
+    fn new(schema: SchemaRef, info: &FlightInfo) -> Self {
+        // The Flight protocol uses -1 for unknown totals.
+        Self {
+            schema,
+            total_records: u64::try_from(info.total_records).ok(),
+            total_bytes: u64::try_from(info.total_bytes).ok(),
+            endpoints: info.endpoint.len(),
+            ordered: info.ordered,
+        }
+    }
+}
+
+/// Action type of the Flight protocol's `CancelFlightInfo` action.
+const CANCEL_FLIGHT_INFO: &str = "CancelFlightInfo";
+
+/// `CancelFlightInfoRequest` from the Flight protocol, which the arrow-flight
+/// version in use predates.
+#[derive(Clone, PartialEq, Message)]
+struct CancelFlightInfoRequest {
+    #[prost(message, optional, tag = "1")]
+    info: Option<FlightInfo>,
+}
+
+/// Flight client for a single endpoint.
+///
+/// Cloning is cheap: clones share the underlying `tonic::Channel` and the
+/// authentication state, so one client can serve many concurrent queries.
+/// The bearer token returned by the handshake is cached and reused until the
+/// server rejects it.
+#[derive(Clone)]
 pub struct SqlFlightClient {
-    token: Option<String>,
-    headers: HashMap<String, String>,
+    token: Arc<RwLock<Option<String>>>,
+    headers: Arc<HashMap<String, String>>,
     client: FlightServiceClient<Channel>,
-    api_key: String,
+    api_key: Option<Arc<str>>,
+    auth_lock: Arc<Mutex<()>>,
+    metrics: Arc<AuthCounters>,
+    origin: Arc<str>,
+    channel_options: ChannelOptions,
+    location_clients: Arc<Mutex<HashMap<String, FlightServiceClient<Channel>>>>,
+    endpoint_concurrency: usize,
+    retry_policy: RetryPolicy,
+    protocol: QueryProtocol,
+    #[cfg(feature = "test-util")]
+    cassette: Option<Cassette>,
+}
+
+/// Counts how often a Flight client authenticated against its endpoint.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct AuthMetrics {
+    /// Total handshakes performed, including refreshes.
+    pub handshakes: u64,
+    /// Handshakes triggered because the server rejected the cached token.
+    pub token_refreshes: u64,
+}
+
+impl std::ops::Add for AuthMetrics {
+    type Output = AuthMetrics;
+
+    fn add(self, rhs: AuthMetrics) -> AuthMetrics {
+        AuthMetrics {
+            handshakes: self.handshakes + rhs.handshakes,
+            token_refreshes: self.token_refreshes + rhs.token_refreshes,
+        }
+    }
 }
 
-fn status_to_arrow_error(status: tonic::Status) -> ArrowError {
-    ArrowError::IpcError(format!("{status:?}"))
+#[derive(Debug, Default)]
+struct AuthCounters {
+    handshakes: AtomicU64,
+    token_refreshes: AtomicU64,
+}
+
+/// Translates a Flight location URI into the URL its channel is dialed at:
+/// `grpc+tls` becomes `https`, while `grpc` and `grpc+tcp` become `http`, as
+/// tonic only enables TLS for `https`.
+fn location_url(uri: &str) -> Result<String> {
+    let unsupported = || {
+        Error::Decode(
+            format!(
+                "Unsupported endpoint location \"{uri}\", expected a grpc, grpc+tcp, \
+                 grpc+tls, http or https URI"
+            )
+            .into(),
+        )
+    };
+    let (scheme, rest) = uri.split_once("://").ok_or_else(unsupported)?;
+    let scheme = match scheme.to_ascii_lowercase().as_str() {
+        "grpc+tls" | "https" => "https",
+        "grpc" | "grpc+tcp" | "http" => "http",
+        _ => return Err(unsupported()),
+    };
+    Ok(format!("{scheme}://{rest}"))
+}
+
+fn invalid_auth_header() -> Error {
+    Error::Auth {
+        message: "Invalid auth header".to_string(),
+        source: None,
+    }
 }
 
 impl SqlFlightClient {
-    pub fn new(chan: Channel, api_key: String) -> Self {
+    /// Creates a client for the channel dialed to `origin`. When `api_key` is
+    /// `None` the handshake is skipped and requests are sent unauthenticated.
+    ///
+    /// `channel_options` are reused to dial endpoint locations that differ
+    /// from `origin`.
+    pub fn new(
+        origin: &str,
+        chan: Channel,
+        api_key: Option<String>,
+        channel_options: ChannelOptions,
+    ) -> Self {
         SqlFlightClient {
-            api_key,
+            api_key: api_key.map(Arc::from),
             client: FlightServiceClient::new(chan),
-            headers: HashMap::default(),
-            token: None,
+            headers: Arc::default(),
+            token: Arc::default(),
+            auth_lock: Arc::default(),
+            metrics: Arc::default(),
+            origin: Arc::from(origin.trim_end_matches('/')),
+            channel_options,
+            location_clients: Arc::default(),
+            endpoint_concurrency: 1,
+            retry_policy: RetryPolicy::default(),
+            protocol: QueryProtocol::default(),
+            #[cfg(feature = "test-util")]
+            cassette: None,
+        }
+    }
+
+    /// Records query results, `FlightInfo` and schema lookups, action and
+    /// upload responses to `cassette`, or serves them from it when it is
+    /// replaying.
+    #[cfg(feature = "test-util")]
+    #[must_use]
+    pub fn with_cassette(mut self, cassette: Cassette) -> Self {
+        self.cassette = Some(cassette);
+        self
+    }
+
+    /// Sets how SQL queries are encoded in Flight requests.
+    #[must_use]
+    pub fn with_query_protocol(mut self, protocol: QueryProtocol) -> Self {
+        self.protocol = protocol;
+        self
+    }
+
+    fn statement_descriptor(&self, query: &str) -> FlightDescriptor {
+        match self.protocol {
+            QueryProtocol::Raw => FlightDescriptor::new_cmd(query.to_string()),
+            QueryProtocol::FlightSql => {
+                let cmd = CommandStatementQuery {
+                    query: query.to_string(),
+                    transaction_id: None,
+                };
+                FlightDescriptor::new_cmd(cmd.as_any().encode_to_vec())
+            }
         }
     }
 
-    async fn handshake(&mut self, username: &str, password: &str) -> Result<Bytes, ArrowError> {
+    /// Sets the policy used to retry transient failures.
+    #[must_use]
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    /// Sets how many endpoints of a query are fetched in parallel.
+    #[must_use]
+    pub fn with_endpoint_concurrency(mut self, endpoint_concurrency: usize) -> Self {
+        self.endpoint_concurrency = endpoint_concurrency.max(1);
+        self
+    }
+
+    async fn handshake(&self, username: &str, password: &str) -> Result<Bytes> {
         let cmd = HandshakeRequest {
             protocol_version: 0,
             payload: Default::default(),
         };
-        let mut req = tonic::Request::new(stream::iter(vec![cmd]));
+        let mut req = self.set_request_headers(tonic::Request::new(stream::iter(vec![cmd])))?;
         let val = BASE64_STANDARD.encode(format!("{username}:{password}"));
         let val = format!("Basic {val}")
             .parse()
-            .map_err(|_| ArrowError::ParseError("Cannot parse header".to_string()))?;
+            .map_err(|_| Error::InvalidApiKey)?;
+        // Replaces any cached bearer token added by `set_request_headers`.
         req.metadata_mut().insert("authorization", val);
-        let req = self.set_request_headers(req)?;
-        let resp = self
-            .client
-            .handshake(req)
-            .await
-            .map_err(|e| ArrowError::IpcError(format!("Can't handshake {e}")))?;
+        self.metrics.handshakes.fetch_add(1, Ordering::Relaxed);
+        let resp = self.client.clone().handshake(req).await?;
         if let Some(auth) = resp.metadata().get("authorization") {
-            let auth = auth
-                .to_str()
-                .map_err(|_| ArrowError::ParseError("Can't read auth header".to_string()))?;
+            let auth = auth.to_str().map_err(|_| invalid_auth_header())?;
             let bearer = "Bearer ";
             if !auth.starts_with(bearer) {
-                Err(ArrowError::ParseError("Invalid auth header!".to_string()))?;
+                Err(invalid_auth_header())?;
             }
             let auth = auth[bearer.len()..].to_string();
-            self.token = Some(auth);
+            *self.token.write().expect("token lock poisoned") = Some(auth);
         }
-        let responses: Vec<HandshakeResponse> = resp
-            .into_inner()
-            .try_collect()
-            .await
-            .map_err(|_| ArrowError::ParseError("Can't collect responses".to_string()))?;
+        let responses: Vec<HandshakeResponse> = resp.into_inner().try_collect().await?;
         let resp = match responses.as_slice() {
             [resp] => resp.payload.clone(),
             [] => Bytes::new(),
-            _ => Err(ArrowError::ParseError(
-                "Multiple handshake responses".to_string(),
-            ))?,
+            _ => Err(Error::decode("Multiple handshake responses"))?,
         };
         Ok(resp)
     }
 
-    async fn authenticate(&mut self) -> std::result::Result<(), Box<dyn Error>> {
-        if self.api_key.split('|').collect::<String>().len() < 2 {
-            return Err("Invalid API key format".into());
+    /// Performs the handshake unless a bearer token is already cached.
+    async fn authenticate(&self) -> Result<()> {
+        let Some(api_key) = self.api_key.clone() else {
+            return Ok(());
+        };
+        if !api_key
+            .split_once('|')
+            .is_some_and(|(app_id, key)| !app_id.is_empty() && !key.is_empty())
+        {
+            return Err(Error::InvalidApiKey);
+        }
+        let _guard = self.auth_lock.lock().await;
+        if self.current_token().is_none() {
+            self.retry_policy
+                .retry("handshake", || self.handshake("", api_key.as_ref()))
+                .await?;
         }
-        self.handshake("", &self.api_key.to_string()).await?;
         Ok(())
     }
 
-    fn set_request_headers<T>(
+    /// Re-handshakes after the server rejected `stale`, unless another task
+    /// already replaced it.
+    async fn refresh_token(&self, stale: Option<String>) -> Result<()> {
+        let Some(api_key) = self.api_key.clone() else {
+            return Ok(());
+        };
+        let _guard = self.auth_lock.lock().await;
+        if self.current_token() == stale {
+            *self.token.write().expect("token lock poisoned") = None;
+            self.metrics.token_refreshes.fetch_add(1, Ordering::Relaxed);
+            self.retry_policy
+                .retry("handshake", || self.handshake("", api_key.as_ref()))
+                .await?;
+        }
+        Ok(())
+    }
+
+    fn current_token(&self) -> Option<String> {
+        self.token.read().expect("token lock poisoned").clone()
+    }
+
+    /// Runs `call` with the cached token, re-handshaking and retrying once if
+    /// the server answers `Unauthenticated`. Transient failures are retried
+    /// according to the retry policy.
+    async fn with_auth<T, F, Fut>(
+        &self,
+        operation: &'static str,
+        client: &FlightServiceClient<Channel>,
+        mut call: F,
+    ) -> Result<T>
+    where
+        F: FnMut(FlightServiceClient<Channel>) -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        #[cfg(feature = "test-util")]
+        if self.cassette.as_ref().is_some_and(Cassette::is_replaying) {
+            return Err(Error::Cassette(format!("{operation} cannot be replayed")));
+        }
+        self.authenticate().await?;
+        let token = self.current_token();
+        let result = self
+            .retry_policy
+            .retry(operation, || call(client.clone()))
+            .await;
+        match result {
+            Err(Error::Auth { .. }) if self.api_key.is_some() => {
+                self.refresh_token(token).await?;
+                self.retry_policy
+                    .retry(operation, || call(client.clone()))
+                    .await
+            }
+            result => result,
+        }
+    }
+
+    /// Returns the client to fetch `endpoint` from, dialing (and caching) a new
+    /// channel when the endpoint advertises a location other than the origin.
+    async fn endpoint_client(
         &self,
-        mut req: tonic::Request<T>,
-    ) -> Result<tonic::Request<T>, ArrowError> {
-        for (k, v) in &self.headers {
+        endpoint: &FlightEndpoint,
+    ) -> Result<FlightServiceClient<Channel>> {
+        let uris: Vec<&str> = endpoint
+            .location
+            .iter()
+            .map(|location| location.uri.trim_end_matches('/'))
+            .collect();
+        if uris.is_empty() || uris.contains(&REUSE_CONNECTION_URI) {
+            return Ok(self.client.clone());
+        }
+        // Locations are alternatives; dial the first one with a known scheme.
+        let (urls, errors): (Vec<_>, Vec<_>) = uris
+            .iter()
+            .map(|uri| location_url(uri))
+            .partition(Result::is_ok);
+        let urls: Vec<String> = urls.into_iter().filter_map(Result::ok).collect();
+        if urls.iter().any(|url| **url == *self.origin) {
+            return Ok(self.client.clone());
+        }
+        let Some(url) = urls.first() else {
+            return Err(errors
+                .into_iter()
+                .find_map(Result::err)
+                .unwrap_or(Error::NoEndpoints));
+        };
+
+        let mut clients = self.location_clients.lock().await;
+        if let Some(client) = clients.get(url) {
+            return Ok(client.clone());
+        }
+        // The domain name override names the origin, not other locations.
+        let mut options = self.channel_options.clone();
+        options.tls.domain_name = None;
+        let chan = new_tls_flight_channel(url, &options).await?;
+        let client = FlightServiceClient::new(chan);
+        clients.insert(url.clone(), client.clone());
+        Ok(client)
+    }
+
+    async fn fetch_endpoint(
+        self,
+        endpoint: FlightEndpoint,
+        deadline: Option<Instant>,
+        trailers: SharedTrailers,
+    ) -> Result<(MetadataMap, RecordBatchStream)> {
+        let client = self.endpoint_client(&endpoint).await?;
+        let Some(tkt) = endpoint.ticket else {
+            return Err(Error::NoEndpoints);
+        };
+        let (headers, response_stream, _) = self
+            .with_auth("do_get", &client, |mut client| {
+                let req = self
+                    .set_request_headers(tkt.clone().into_request())
+                    .and_then(|req| with_deadline(req, deadline));
+                async move { Ok(client.do_get(req?).await?) }
+            })
+            .await?
+            .into_parts();
+
+        let stream = FlightRecordBatchStream::new_from_flight_data(
+            collect_trailers(response_stream, trailers).map_err(FlightError::Tonic),
+        )
+        .map_err(Error::from)
+        .boxed();
+        Ok((headers, stream))
+    }
+
+    pub fn auth_metrics(&self) -> AuthMetrics {
+        AuthMetrics {
+            handshakes: self.metrics.handshakes.load(Ordering::Relaxed),
+            token_refreshes: self.metrics.token_refreshes.load(Ordering::Relaxed),
+        }
+    }
+
+    fn set_request_headers<T>(&self, mut req: tonic::Request<T>) -> Result<tonic::Request<T>> {
+        for (k, v) in self.headers.iter() {
             let k = AsciiMetadataKey::from_str(k.as_str()).map_err(|e| {
-                ArrowError::ParseError(format!("Cannot convert header key \"{k}\": {e}"))
+                Error::config_with_source(format!("Cannot convert header key \"{k}\""), e)
             })?;
             let v = v.parse().map_err(|e| {
-                ArrowError::ParseError(format!("Cannot convert header value \"{v}\": {e}"))
+                Error::config_with_source(format!("Cannot convert header value \"{v}\""), e)
             })?;
             req.metadata_mut().insert(k, v);
         }
-        if let Some(token) = &self.token {
-            let val = format!("Bearer {token}").parse().map_err(|e| {
-                ArrowError::ParseError(format!("Cannot convert token to header value: {e}"))
-            })?;
+        if let Some(token) = self.token.read().expect("token lock poisoned").as_ref() {
+            let val = format!("Bearer {token}")
+                .parse()
+                .map_err(|_| invalid_auth_header())?;
             req.metadata_mut().insert("authorization", val);
         }
         Ok(req)
     }
 
-    pub async fn query(
-        &mut self,
-        query: &str,
-    ) -> std::result::Result<FlightRecordBatchStream, Box<dyn Error>> {
-        self.authenticate().await?;
-
-        let descriptor = FlightDescriptor::new_cmd(query.to_string());
-        let req = self.set_request_headers(descriptor.into_request())?;
+    /// Runs `query` and returns a stream over the results of every endpoint.
+    ///
+    /// Endpoints are fetched one after another unless an endpoint concurrency
+    /// above one is configured, in which case that many are fetched in
+    /// parallel. Batches keep the endpoint order when the server marks the
+    /// result as ordered, and are interleaved otherwise.
+    pub async fn query(&self, query: &str) -> Result<QueryResult> {
+        self.query_with_options(query, &QueryOptions::default())
+            .await
+    }
 
-        let info = self
-            .client
-            .get_flight_info(req)
+    /// Runs `query` like [`query`](Self::query), bounded by the timeout and
+    /// cancellation token of `options`.
+    pub async fn query_with_options(
+        &self,
+        query: &str,
+        options: &QueryOptions,
+    ) -> Result<QueryResult> {
+        self.execute_with_options(self.statement_descriptor(query), options)
             .await
-            .map_err(status_to_arrow_error)?
-            .into_inner();
-
-        for ep in info.endpoint {
-            if let Some(tkt) = ep.ticket {
-                let req = tkt.into_request();
-                let req = self.set_request_headers(req)?;
-                let (md, response_stream, _ext) = self
-                    .client
-                    .do_get(req)
-                    .await
-                    .map_err(status_to_arrow_error)?
-                    .into_parts();
+    }
 
-                return Ok(FlightRecordBatchStream::new_from_flight_data(
-                    response_stream.map_err(FlightError::Tonic),
+    /// Gets the `FlightInfo` for `descriptor` and streams the results of all
+    /// its endpoints, bounded by the timeout and cancellation token of `options`.
+    pub async fn execute_with_options(
+        &self,
+        descriptor: FlightDescriptor,
+        options: &QueryOptions,
+    ) -> Result<QueryResult> {
+        #[cfg(feature = "test-util")]
+        if let Some(cassette) = &self.cassette {
+            if cassette.is_replaying() {
+                let deadline = options.deadline();
+                let cancel = options.cancellation_token.clone();
+                let result = bounded(
+                    async { cassette.replay_query(&descriptor) },
+                    deadline,
+                    cancel.as_ref(),
                 )
-                .with_headers(md));
+                .await?;
+                return Ok(result.bounded(deadline, cancel));
+            }
+            let result = self.fetch_query(descriptor.clone(), options).await?;
+            return cassette.record_query(&descriptor, result).await;
+        }
+        self.fetch_query(descriptor, options).await
+    }
+
+    async fn fetch_query(
+        &self,
+        descriptor: FlightDescriptor,
+        options: &QueryOptions,
+    ) -> Result<QueryResult> {
+        let deadline = options.deadline();
+        let cancel = options.cancellation_token.clone();
+        let trailers = SharedTrailers::default();
+
+        let info = bounded(
+            self.get_flight_info(&descriptor, deadline),
+            deadline,
+            cancel.as_ref(),
+        )
+        .await?;
+        // From here on the server may be working on the query, so any failure,
+        // including a timeout or cancellation, also cancels it there.
+        let fetched = bounded(
+            self.fetch_all(info.clone(), deadline, trailers.clone()),
+            deadline,
+            cancel.as_ref(),
+        )
+        .await;
+        let (headers, mut stream) = match fetched {
+            Ok(fetched) => fetched,
+            Err(err) => {
+                self.cancel_flight_info(info);
+                return Err(err);
+            }
+        };
+        let schema = if info.schema.is_empty() {
+            None
+        } else {
+            Some(Arc::new(Schema::try_from(IpcMessage(info.schema.clone()))?))
+        };
+        let total_records = u64::try_from(info.total_records).ok();
+        let total_bytes = u64::try_from(info.total_bytes).ok();
+        if deadline.is_some() || cancel.is_some() {
+            stream = BoundedStream {
+                inner: stream,
+                sleep: deadline.map(|deadline| Box::pin(tokio::time::sleep_until(deadline))),
+                cancelled: cancel.map(|token| Box::pin(token.cancelled_owned())),
+                on_abort: Some((self.clone(), info)),
+            }
+            .boxed();
+        }
+        Ok(QueryResult::new(
+            stream,
+            schema,
+            total_records,
+            total_bytes,
+            headers,
+            trailers,
+        ))
+    }
+
+    async fn fetch_all(
+        &self,
+        info: FlightInfo,
+        deadline: Option<Instant>,
+        trailers: SharedTrailers,
+    ) -> Result<(MetadataMap, RecordBatchStream)> {
+        let mut endpoints = info.endpoint.into_iter().filter(|ep| ep.ticket.is_some());
+        let Some(first) = endpoints.next() else {
+            return Err(Error::NoEndpoints);
+        };
+        // Open the first endpoint eagerly so that errors surface from `query`.
+        let (headers, first) = self
+            .clone()
+            .fetch_endpoint(first, deadline, trailers.clone())
+            .await?;
+
+        let this = self.clone();
+        let rest = stream::iter(endpoints.collect::<Vec<_>>()).map(move |endpoint| {
+            this.clone()
+                .fetch_endpoint(endpoint, deadline, trailers.clone())
+                .map_ok(|(_, stream)| stream)
+        });
+        let concurrency = self.endpoint_concurrency;
+        let stream = if info.ordered || concurrency == 1 {
+            let streams = stream::once(async { Ok(first) }).chain(rest.buffered(concurrency));
+            streams.try_flatten().boxed()
+        } else {
+            let streams =
+                stream::once(async { Ok(first) }).chain(rest.buffer_unordered(concurrency));
+            streams.try_flatten_unordered(concurrency).boxed()
+        };
+        Ok((headers, stream))
+    }
+
+    async fn get_flight_info(
+        &self,
+        descriptor: &FlightDescriptor,
+        deadline: Option<Instant>,
+    ) -> Result<FlightInfo> {
+        self.with_auth("get_flight_info", &self.client, |mut client| {
+            let req = self
+                .set_request_headers(descriptor.clone().into_request())
+                .and_then(|req| with_deadline(req, deadline));
+            async move { Ok(client.get_flight_info(req?).await?.into_inner()) }
+        })
+        .await
+    }
+
+    /// Describes the results of `query` without fetching them. The schema is
+    /// taken from the `FlightInfo`, falling back to the `GetSchema` call when
+    /// the server leaves it empty.
+    pub async fn query_info(&self, query: &str) -> Result<QueryInfo> {
+        let descriptor = self.statement_descriptor(query);
+        let info = self.describe_query(&descriptor).await?;
+        let schema = if info.schema.is_empty() {
+            let result = self.query_schema_result(&descriptor).await?;
+            Schema::try_from(&result)?
+        } else {
+            Schema::try_from(IpcMessage(info.schema.clone()))?
+        };
+        Ok(QueryInfo::new(Arc::new(schema), &info))
+    }
+
+    async fn describe_query(&self, descriptor: &FlightDescriptor) -> Result<FlightInfo> {
+        #[cfg(feature = "test-util")]
+        if let Some(cassette) = self.cassette.as_ref().filter(|c| c.is_replaying()) {
+            return cassette.replay_flight_info(descriptor);
+        }
+        let info = self.get_flight_info(descriptor, None).await?;
+        #[cfg(feature = "test-util")]
+        if let Some(cassette) = &self.cassette {
+            cassette.record_flight_info(descriptor, &info);
+        }
+        Ok(info)
+    }
+
+    async fn query_schema_result(&self, descriptor: &FlightDescriptor) -> Result<SchemaResult> {
+        #[cfg(feature = "test-util")]
+        if let Some(cassette) = self.cassette.as_ref().filter(|c| c.is_replaying()) {
+            return cassette.replay_schema(descriptor);
+        }
+        let result = self
+            .with_auth("get_schema", &self.client, |mut client| {
+                let req = self.set_request_headers(descriptor.clone().into_request());
+                async move { Ok(client.get_schema(req?).await?.into_inner()) }
+            })
+            .await?;
+        #[cfg(feature = "test-util")]
+        if let Some(cassette) = &self.cassette {
+            cassette.record_schema(descriptor, &result);
+        }
+        Ok(result)
+    }
+
+    /// Runs a Flight action and collects its results.
+    pub async fn do_action(&self, action: Action) -> Result<Vec<arrow_flight::Result>> {
+        #[cfg(feature = "test-util")]
+        if let Some(cassette) = self.cassette.as_ref().filter(|c| c.is_replaying()) {
+            return cassette.replay_action(&action);
+        }
+        let results: Vec<arrow_flight::Result> = self
+            .with_auth("do_action", &self.client, |mut client| {
+                let req = self.set_request_headers(action.clone().into_request());
+                async move {
+                    let results = client.do_action(req?).await?.into_inner();
+                    Ok(results.try_collect().await?)
+                }
+            })
+            .await?;
+        #[cfg(feature = "test-util")]
+        if let Some(cassette) = &self.cassette {
+            cassette.record_action(&action, &results);
+        }
+        Ok(results)
+    }
+
+    /// Uploads `data` with `DoPut` and collects the server's acknowledgements.
+    pub async fn do_put(&self, data: Vec<FlightData>) -> Result<Vec<PutResult>> {
+        #[cfg(feature = "test-util")]
+        if let Some(cassette) = self.cassette.as_ref().filter(|c| c.is_replaying()) {
+            return cassette.replay_put(&data);
+        }
+        let results: Vec<PutResult> = self
+            .with_auth("do_put", &self.client, |mut client| {
+                let req = self.set_request_headers(stream::iter(data.clone()).into_request());
+                async move {
+                    let results = client.do_put(req?).await?.into_inner();
+                    Ok(results.try_collect().await?)
+                }
+            })
+            .await?;
+        #[cfg(feature = "test-util")]
+        if let Some(cassette) = &self.cassette {
+            cassette.record_put(&data, &results);
+        }
+        Ok(results)
+    }
+
+    /// Asks the server, in the background, to stop working on `info`.
+    /// Failures are ignored as not every server supports cancellation.
+    fn cancel_flight_info(&self, info: FlightInfo) {
+        let Ok(handle) = tokio::runtime::Handle::try_current() else {
+            return;
+        };
+        let this = self.clone();
+        handle.spawn(async move {
+            let body = CancelFlightInfoRequest { info: Some(info) }.encode_to_vec();
+            let action = Action::new(CANCEL_FLIGHT_INFO, body);
+            if let Ok(req) = this.set_request_headers(action.into_request()) {
+                let _ = this.client.clone().do_action(req).await;
+            }
+        });
+    }
+}
+
+/// Passes `stream` through, merging its trailers into `sink` once it ends.
+fn collect_trailers(
+    stream: Streaming<FlightData>,
+    sink: SharedTrailers,
+) -> impl Stream<Item = std::result::Result<FlightData, tonic::Status>> {
+    stream::unfold(Some(stream), move |state| {
+        let sink = sink.clone();
+        async move {
+            let mut stream = state?;
+            match stream.message().await {
+                Ok(Some(data)) => Some((Ok(data), Some(stream))),
+                Ok(None) => {
+                    if let Ok(Some(trailers)) = stream.trailers().await {
+                        let mut sink = sink.lock().expect("trailers lock poisoned");
+                        let mut merged = sink.take().unwrap_or_default().into_headers();
+                        merged.extend(trailers.into_headers());
+                        *sink = Some(MetadataMap::from_headers(merged));
+                    }
+                    None
+                }
+                Err(status) => Some((Err(status), None)),
+            }
+        }
+    })
+}
+
+/// Sets the gRPC timeout of `req` to the time left until `deadline`.
+fn with_deadline<T>(
+    mut req: tonic::Request<T>,
+    deadline: Option<Instant>,
+) -> Result<tonic::Request<T>> {
+    if let Some(deadline) = deadline {
+        req.set_timeout(remaining(deadline)?);
+    }
+    Ok(req)
+}
+
+/// Ends the wrapped stream with [`Error::Timeout`] or [`Error::Cancelled`]
+/// once the deadline passes or the token is cancelled, and cancels the query
+/// on the server.
+struct BoundedStream {
+    inner: RecordBatchStream,
+    sleep: Option<Pin<Box<Sleep>>>,
+    cancelled: Option<Pin<Box<WaitForCancellationFutureOwned>>>,
+    on_abort: Option<(SqlFlightClient, FlightInfo)>,
+}
+
+impl BoundedStream {
+    fn abort(&mut self, error: Error) -> Poll<Option<Result<RecordBatch>>> {
+        if let Some((client, info)) = self.on_abort.take() {
+            client.cancel_flight_info(info);
+            return Poll::Ready(Some(Err(error)));
+        }
+        Poll::Ready(None)
+    }
+}
+
+impl Stream for BoundedStream {
+    type Item = Result<RecordBatch>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        if self.on_abort.is_none() {
+            return Poll::Ready(None);
+        }
+        if let Some(cancelled) = self.cancelled.as_mut() {
+            if cancelled.as_mut().poll(cx).is_ready() {
+                return self.abort(Error::Cancelled);
+            }
+        }
+        if let Some(sleep) = self.sleep.as_mut() {
+            if sleep.as_mut().poll(cx).is_ready() {
+                return self.abort(Error::Timeout);
+            }
+        }
+        match self.inner.poll_next_unpin(cx) {
+            Poll::Ready(None) => {
+                self.on_abort = None;
+                Poll::Ready(None)
             }
+            poll => poll,
         }
-        Err("No endpoints found".into())
     }
 }
@@ -1,13 +1,44 @@
 #![doc = include_str!("../README.md")]
 
+#[cfg(feature = "test-util")]
+mod cassette;
+mod catalog;
 mod client;
 mod config;
+mod de;
+mod error;
+mod export;
 mod flight;
+mod options;
+mod pair;
+mod prepared;
 mod prices;
+mod result;
+mod retry;
 mod tls;
 
-pub use client::SpiceClient as Client;
-pub use prices::{HistoricalPriceData, LatestPriceDetail, LatestPricesResponse};
+#[cfg(feature = "test-util")]
+pub mod testing;
+
+pub use catalog::{DatabaseSchema, TableFilter, TableInfo};
+pub use client::{SpiceClient as Client, SpiceClientBuilder};
+pub use de::from_record_batch;
+pub use error::{Error, Result};
+#[cfg(feature = "parquet")]
+pub use export::ParquetCompression;
+pub use export::{write_batches, ExportFormat};
+pub use flight::{AuthMetrics, QueryInfo};
+pub use options::{QueryOptions, QueryProtocol};
+pub use pair::TradingPair;
+pub use prepared::{ParameterValue, PreparedStatement};
+pub use prices::{
+    historical_prices_from_record_batch, historical_prices_to_record_batch, prices_schema,
+    Granularity, HistoricalPriceData, LatestPriceDetail, LatestPricesResponse, PriceTick,
+};
+pub use result::{QueryResult, QUERY_ID_HEADER};
+pub use retry::{RetryEvent, RetryPolicy};
+pub use tls::TlsRoots;
 
 // Further public exports and integrations
 pub use futures::StreamExt;
+pub use tokio_util::sync::CancellationToken;
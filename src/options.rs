@@ -0,0 +1,141 @@
+use std::future::Future;
+use std::time::Duration;
+
+use futures::stream::{self, BoxStream};
+use futures::StreamExt;
+use tokio::time::Instant;
+use tokio_util::sync::CancellationToken;
+
+use crate::error::{Error, Result};
+
+/// How SQL queries are encoded in Flight requests.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum QueryProtocol {
+    /// Sends the SQL string as the raw `FlightDescriptor` command, as expected
+    /// by the Spice.ai cloud Flight and Firecache endpoints.
+    #[default]
+    Raw,
+    /// Sends a Flight SQL `CommandStatementQuery`, understood by any Flight SQL
+    /// server such as the Spice OSS runtime.
+    FlightSql,
+}
+
+/// Per-request settings accepted by [`Client::query_with_options`](crate::Client::query_with_options),
+/// [`Client::fire_query_with_options`](crate::Client::fire_query_with_options)
+/// and the `_with_options` variants of the prices calls.
+///
+/// For queries, the timeout bounds the whole query, from the handshake to the
+/// last batch, and is propagated to the server as the gRPC `grpc-timeout`.
+/// Cancelling the token aborts the query and the returned stream; in both
+/// cases the client asks the server to cancel the query with a Flight
+/// `CancelFlightInfo` action, which servers that do not support it ignore.
+///
+/// For prices calls, the timeout bounds the call, retries included, and is
+/// set on each HTTP request. Cancelling the token aborts the call, or ends
+/// the returned stream with [`Error::Cancelled`].
+/// ```
+/// use spiceai::{CancellationToken, QueryOptions};
+/// use std::time::Duration;
+///
+/// let token = CancellationToken::new();
+/// let options = QueryOptions::new()
+///     .with_timeout(Duration::from_secs(30))
+///     .with_cancellation_token(token.clone());
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct QueryOptions {
+    pub(crate) timeout: Option<Duration>,
+    pub(crate) cancellation_token: Option<CancellationToken>,
+}
+
+impl QueryOptions {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the maximum time the query, including streaming its results, may take.
+    #[must_use]
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Sets a token that aborts the query when cancelled.
+    #[must_use]
+    pub fn with_cancellation_token(mut self, token: CancellationToken) -> Self {
+        self.cancellation_token = Some(token);
+        self
+    }
+}
+
+impl QueryOptions {
+    /// Returns when an operation starting now must complete by.
+    pub(crate) fn deadline(&self) -> Option<Instant> {
+        self.timeout.map(|timeout| Instant::now() + timeout)
+    }
+}
+
+/// Returns the time left until `deadline`, or [`Error::Timeout`] if it passed.
+pub(crate) fn remaining(deadline: Instant) -> Result<Duration> {
+    let remaining = deadline.saturating_duration_since(Instant::now());
+    if remaining.is_zero() {
+        return Err(Error::Timeout);
+    }
+    Ok(remaining)
+}
+
+/// Runs `fut` until it completes, `deadline` passes or `cancel` is cancelled.
+/// Errors returned once the deadline passed, such as the gRPC or HTTP timeout
+/// of a request, are reported as [`Error::Timeout`].
+pub(crate) async fn bounded<T>(
+    fut: impl Future<Output = Result<T>>,
+    deadline: Option<Instant>,
+    cancel: Option<&CancellationToken>,
+) -> Result<T> {
+    let timed = async {
+        match deadline {
+            Some(deadline) => match tokio::time::timeout_at(deadline, fut).await {
+                Ok(Err(_)) if Instant::now() >= deadline => Err(Error::Timeout),
+                Ok(result) => result,
+                Err(_) => Err(Error::Timeout),
+            },
+            None => fut.await,
+        }
+    };
+    match cancel {
+        Some(cancel) => {
+            tokio::select! {
+                biased;
+                () = cancel.cancelled() => Err(Error::Cancelled),
+                result = timed => result,
+            }
+        }
+        None => timed.await,
+    }
+}
+
+/// Ends `stream` with [`Error::Timeout`] or [`Error::Cancelled`] once
+/// `deadline` passes or `cancel` is cancelled.
+pub(crate) fn bounded_stream<T: Send + 'static>(
+    stream: BoxStream<'static, Result<T>>,
+    deadline: Option<Instant>,
+    cancel: Option<CancellationToken>,
+) -> BoxStream<'static, Result<T>> {
+    if deadline.is_none() && cancel.is_none() {
+        return stream;
+    }
+    stream::unfold(Some(stream), move |state| {
+        let cancel = cancel.clone();
+        async move {
+            let mut stream = state?;
+            let next = async { Ok(stream.next().await) };
+            match bounded(next, deadline, cancel.as_ref()).await {
+                Ok(Some(item)) => Some((item, Some(stream))),
+                Ok(None) => None,
+                Err(e) => Some((Err(e), None)),
+            }
+        }
+    })
+    .boxed()
+}
@@ -0,0 +1,89 @@
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::error::{Error, Result};
+
+/// A trading pair such as `BTC-USD`: `base` priced in `quote`.
+///
+/// Both assets are upper-cased ASCII alphanumeric symbols, so pairs parsed
+/// from `"btc-usd"` and `"BTC-USD"` are equal.
+/// ```
+/// use spiceai::TradingPair;
+///
+/// let pair: TradingPair = "btc-usdc".parse().unwrap();
+/// assert_eq!(pair.base(), "BTC");
+/// assert_eq!(pair.quote(), "USDC");
+/// assert_eq!(pair.to_string(), "BTC-USDC");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct TradingPair {
+    base: String,
+    quote: String,
+}
+
+impl TradingPair {
+    /// Creates the pair of `base` priced in `quote`.
+    pub fn new(base: &str, quote: &str) -> Result<Self> {
+        Ok(Self {
+            base: parse_symbol(base, "base")?,
+            quote: parse_symbol(quote, "quote")?,
+        })
+    }
+
+    /// Returns the asset being priced.
+    #[must_use]
+    pub fn base(&self) -> &str {
+        &self.base
+    }
+
+    /// Returns the asset the price is expressed in.
+    #[must_use]
+    pub fn quote(&self) -> &str {
+        &self.quote
+    }
+}
+
+fn parse_symbol(symbol: &str, role: &str) -> Result<String> {
+    if symbol.is_empty() || !symbol.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return Err(Error::InvalidArgument {
+            message: format!(
+                "invalid {role} asset \"{symbol}\", expected ASCII letters and digits"
+            ),
+        });
+    }
+    Ok(symbol.to_ascii_uppercase())
+}
+
+impl FromStr for TradingPair {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().split_once('-') {
+            Some((base, quote)) => TradingPair::new(base, quote),
+            None => Err(Error::InvalidArgument {
+                message: format!("invalid trading pair \"{s}\", expected BASE-QUOTE"),
+            }),
+        }
+    }
+}
+
+impl fmt::Display for TradingPair {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}-{}", self.base, self.quote)
+    }
+}
+
+impl Serialize for TradingPair {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for TradingPair {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        let pair = String::deserialize(deserializer)?;
+        pair.parse().map_err(serde::de::Error::custom)
+    }
+}
@@ -0,0 +1,315 @@
+use std::sync::Arc;
+
+use arrow::array::{
+    new_null_array, ArrayRef, BinaryArray, BooleanArray, Float64Array, Int64Array, NullArray,
+    StringArray, TimestampMicrosecondArray, UInt64Array,
+};
+use arrow::compute::{cast_with_options, CastOptions};
+use arrow::datatypes::{DataType, Field, Schema, SchemaRef};
+use arrow::ipc::writer::IpcWriteOptions;
+use arrow::record_batch::RecordBatch;
+use arrow_flight::encode::FlightDataEncoderBuilder;
+use arrow_flight::sql::{
+    ActionClosePreparedStatementRequest, ActionCreatePreparedStatementRequest,
+    ActionCreatePreparedStatementResult, Any, CommandPreparedStatementQuery, ProstMessageExt,
+};
+use arrow_flight::{Action, FlightDescriptor, IpcMessage};
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use futures::{stream, TryStreamExt};
+use prost::Message;
+
+use crate::error::{Error, Result};
+use crate::flight::SqlFlightClient;
+use crate::options::QueryOptions;
+use crate::result::QueryResult;
+
+const CREATE_PREPARED_STATEMENT: &str = "CreatePreparedStatement";
+const CLOSE_PREPARED_STATEMENT: &str = "ClosePreparedStatement";
+
+/// Fails on values that do not fit the target type instead of nulling them.
+const STRICT_CAST: CastOptions<'static> = CastOptions {
+    safe: false,
+    format_options: arrow::util::display::FormatOptions::new(),
+};
+
+/// A value bound to a prepared statement parameter with
+/// [`PreparedStatement::bind_values`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParameterValue {
+    Null,
+    Boolean(bool),
+    Int64(i64),
+    UInt64(u64),
+    Float64(f64),
+    Utf8(String),
+    Binary(Vec<u8>),
+    Timestamp(DateTime<Utc>),
+}
+
+impl ParameterValue {
+    fn to_array(&self) -> ArrayRef {
+        match self {
+            ParameterValue::Null => Arc::new(NullArray::new(1)),
+            ParameterValue::Boolean(v) => Arc::new(BooleanArray::from(vec![*v])),
+            ParameterValue::Int64(v) => Arc::new(Int64Array::from(vec![*v])),
+            ParameterValue::UInt64(v) => Arc::new(UInt64Array::from(vec![*v])),
+            ParameterValue::Float64(v) => Arc::new(Float64Array::from(vec![*v])),
+            ParameterValue::Utf8(v) => Arc::new(StringArray::from(vec![v.as_str()])),
+            ParameterValue::Binary(v) => Arc::new(BinaryArray::from(vec![v.as_slice()])),
+            ParameterValue::Timestamp(v) => Arc::new(
+                TimestampMicrosecondArray::from(vec![v.timestamp_micros()]).with_timezone("UTC"),
+            ),
+        }
+    }
+}
+
+macro_rules! impl_parameter_value_from {
+    ($($t:ty => $variant:ident),* $(,)?) => {
+        $(
+            impl From<$t> for ParameterValue {
+                fn from(value: $t) -> Self {
+                    ParameterValue::$variant(value.into())
+                }
+            }
+        )*
+    };
+}
+
+impl_parameter_value_from!(
+    bool => Boolean,
+    i8 => Int64,
+    i16 => Int64,
+    i32 => Int64,
+    i64 => Int64,
+    u8 => UInt64,
+    u16 => UInt64,
+    u32 => UInt64,
+    u64 => UInt64,
+    f32 => Float64,
+    f64 => Float64,
+    String => Utf8,
+    &str => Utf8,
+    Vec<u8> => Binary,
+    &[u8] => Binary,
+    DateTime<Utc> => Timestamp,
+);
+
+impl<T: Into<ParameterValue>> From<Option<T>> for ParameterValue {
+    fn from(value: Option<T>) -> Self {
+        value.map_or(ParameterValue::Null, Into::into)
+    }
+}
+
+/// A Flight SQL prepared statement created with
+/// [`Client::prepare`](crate::Client::prepare).
+///
+/// Parameters are bound separately from the SQL text, so user-supplied values
+/// never need to be formatted into the query. The statement can be executed
+/// repeatedly, with new parameters bound between executions. The server-side
+/// handle is released by [`close`](Self::close), or in the background when
+/// the statement is dropped.
+/// ```no_run
+/// # use spiceai::Client;
+/// #
+/// # #[tokio::main]
+/// # async fn main() {
+/// #  let client = Client::new("API_KEY").await.unwrap();
+/// let mut statement = client
+///     .prepare("SELECT * FROM eth.recent_blocks WHERE number > $1 LIMIT 10;")
+///     .await
+///     .unwrap();
+/// statement.bind_values([18_000_000_i64]).unwrap();
+/// let data = statement.execute().await;
+/// statement.close().await.unwrap();
+/// # }
+/// ```
+pub struct PreparedStatement {
+    client: SqlFlightClient,
+    handle: Bytes,
+    dataset_schema: SchemaRef,
+    parameter_schema: SchemaRef,
+    parameters: Option<RecordBatch>,
+    closed: bool,
+}
+
+impl PreparedStatement {
+    pub(crate) async fn create(client: SqlFlightClient, query: &str) -> Result<Self> {
+        let request = ActionCreatePreparedStatementRequest {
+            query: query.to_string(),
+            transaction_id: None,
+        };
+        let action = Action::new(CREATE_PREPARED_STATEMENT, request.as_any().encode_to_vec());
+        let results = client.do_action(action).await?;
+        let result = results
+            .first()
+            .ok_or_else(|| Error::decode("Empty CreatePreparedStatement response"))?;
+        let any = Any::decode(&*result.body).map_err(|e| Error::Decode(Box::new(e)))?;
+        let result: ActionCreatePreparedStatementResult = any
+            .unpack()?
+            .ok_or_else(|| Error::decode("Unexpected CreatePreparedStatement response"))?;
+
+        Ok(Self {
+            client,
+            handle: result.prepared_statement_handle,
+            dataset_schema: decode_schema(result.dataset_schema)?,
+            parameter_schema: decode_schema(result.parameter_schema)?,
+            parameters: None,
+            closed: false,
+        })
+    }
+
+    /// Returns the schema of the statement's results, if the server provided one.
+    #[must_use]
+    pub fn dataset_schema(&self) -> &SchemaRef {
+        &self.dataset_schema
+    }
+
+    /// Returns the schema of the statement's parameters, if the server provided one.
+    #[must_use]
+    pub fn parameter_schema(&self) -> &SchemaRef {
+        &self.parameter_schema
+    }
+
+    /// Binds parameters from a record batch, one column per parameter and
+    /// one row per execution of the statement.
+    pub fn bind(&mut self, parameters: RecordBatch) -> Result<&mut Self> {
+        let expected = self.parameter_schema.fields().len();
+        if expected > 0 && parameters.num_columns() != expected {
+            return Err(Error::InvalidArgument {
+                message: format!(
+                    "Expected {expected} parameters, got {}",
+                    parameters.num_columns()
+                ),
+            });
+        }
+        self.parameters = Some(parameters);
+        Ok(self)
+    }
+
+    /// Binds one value per parameter, in order. Values are cast to the
+    /// parameter types advertised by the server, and rejected when they
+    /// cannot be represented in them.
+    /// ```
+    /// # use spiceai::{ParameterValue, PreparedStatement};
+    /// # fn bind(statement: &mut PreparedStatement) -> spiceai::Result<()> {
+    /// statement.bind_values([ParameterValue::from("BTC-USD"), 100_i64.into(), None::<f64>.into()])?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn bind_values<V: Into<ParameterValue>>(
+        &mut self,
+        values: impl IntoIterator<Item = V>,
+    ) -> Result<&mut Self> {
+        let values: Vec<ParameterValue> = values.into_iter().map(Into::into).collect();
+        let declared = self.parameter_schema.fields();
+        if !declared.is_empty() && declared.len() != values.len() {
+            return Err(Error::InvalidArgument {
+                message: format!(
+                    "Expected {} parameters, got {}",
+                    declared.len(),
+                    values.len()
+                ),
+            });
+        }
+
+        let mut fields = Vec::with_capacity(values.len());
+        let mut columns = Vec::with_capacity(values.len());
+        for (i, value) in values.iter().enumerate() {
+            let (name, data_type) = match declared.get(i) {
+                Some(field) => (field.name().clone(), Some(field.data_type())),
+                None => (format!("${}", i + 1), None),
+            };
+            let array = match (value, data_type) {
+                (ParameterValue::Null, Some(data_type)) => new_null_array(data_type, 1),
+                (value, Some(data_type)) if *data_type != DataType::Null => {
+                    cast_with_options(&value.to_array(), data_type, &STRICT_CAST).map_err(|e| {
+                        Error::InvalidArgument {
+                            message: format!(
+                                "Cannot bind {value:?} to parameter {name} of type {data_type}: {e}"
+                            ),
+                        }
+                    })?
+                }
+                (value, _) => value.to_array(),
+            };
+            fields.push(Field::new(name, array.data_type().clone(), true));
+            columns.push(array);
+        }
+
+        let batch = RecordBatch::try_new(Arc::new(Schema::new(fields)), columns)?;
+        self.bind(batch)
+    }
+
+    /// Executes the statement with the bound parameters.
+    pub async fn execute(&self) -> Result<QueryResult> {
+        self.execute_with_options(&QueryOptions::default()).await
+    }
+
+    /// Executes the statement with the bound parameters, bounded by the
+    /// timeout and cancellation token of `options`.
+    pub async fn execute_with_options(&self, options: &QueryOptions) -> Result<QueryResult> {
+        if let Some(parameters) = &self.parameters {
+            self.put_parameters(parameters).await?;
+        }
+        self.client
+            .execute_with_options(self.descriptor(), options)
+            .await
+    }
+
+    /// Releases the statement on the server.
+    pub async fn close(mut self) -> Result<()> {
+        self.closed = true;
+        self.client.do_action(self.close_action()).await?;
+        Ok(())
+    }
+
+    fn descriptor(&self) -> FlightDescriptor {
+        let cmd = CommandPreparedStatementQuery {
+            prepared_statement_handle: self.handle.clone(),
+        };
+        FlightDescriptor::new_cmd(cmd.as_any().encode_to_vec())
+    }
+
+    fn close_action(&self) -> Action {
+        let request = ActionClosePreparedStatementRequest {
+            prepared_statement_handle: self.handle.clone(),
+        };
+        Action::new(CLOSE_PREPARED_STATEMENT, request.as_any().encode_to_vec())
+    }
+
+    async fn put_parameters(&self, parameters: &RecordBatch) -> Result<()> {
+        let data = FlightDataEncoderBuilder::new()
+            .with_flight_descriptor(Some(self.descriptor()))
+            .with_schema(parameters.schema())
+            .with_options(IpcWriteOptions::default())
+            .build(stream::iter([Ok(parameters.clone())]))
+            .try_collect::<Vec<_>>()
+            .await?;
+        self.client.do_put(data).await?;
+        Ok(())
+    }
+}
+
+impl Drop for PreparedStatement {
+    fn drop(&mut self) {
+        if self.closed {
+            return;
+        }
+        let Ok(handle) = tokio::runtime::Handle::try_current() else {
+            return;
+        };
+        let client = self.client.clone();
+        let action = self.close_action();
+        handle.spawn(async move {
+            let _ = client.do_action(action).await;
+        });
+    }
+}
+
+fn decode_schema(bytes: Bytes) -> Result<SchemaRef> {
+    if bytes.is_empty() {
+        return Ok(Arc::new(Schema::empty()));
+    }
+    Ok(Arc::new(Schema::try_from(IpcMessage(bytes))?))
+}
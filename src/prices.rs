@@ -1,14 +1,176 @@
-use std::collections::HashMap;
-use std::error::Error;
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
 
+use arrow::array::{ArrayRef, Float64Array, StringArray, TimestampMillisecondArray};
+use arrow::datatypes::{DataType, Field, Schema, SchemaRef, TimeUnit};
+use arrow::record_batch::RecordBatch;
 use chrono::{DateTime, Utc};
-use reqwest::{Response, StatusCode};
+use futures::stream::{self, BoxStream};
+use futures::{StreamExt, TryStreamExt};
+use reqwest::{header::RETRY_AFTER, Response, StatusCode};
 use serde::{de::DeserializeOwned, Deserialize};
+use tokio::time::Instant;
 
+#[cfg(feature = "test-util")]
+use crate::cassette::Cassette;
 use crate::config::HTTPS_ADDR;
+use crate::de::from_record_batch;
+use crate::error::{Error, Result};
+use crate::options::{bounded, bounded_stream, remaining, QueryOptions};
+use crate::pair::TradingPair;
+use crate::retry::RetryPolicy;
+use crate::tls::{http_tls_config, ChannelOptions};
 
-#[derive(Debug, Deserialize)]
+/// Intervals requested at a time by
+/// [`PricesClient::stream_historical_prices`] when no maximum is configured,
+/// e.g. one day of one-minute prices.
+const HISTORICAL_WINDOW_INTERVALS: u32 = 1440;
+
+/// Longest a price subscription backs off for when it is rate limited
+/// without a `Retry-After`.
+const MAX_SUBSCRIPTION_BACKOFF: Duration = Duration::from_secs(60);
+
+/// The interval between historical prices.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[non_exhaustive]
+pub enum Granularity {
+    /// One minute, `1m`.
+    Minute,
+    /// Five minutes, `5m`.
+    FiveMinutes,
+    /// Fifteen minutes, `15m`.
+    FifteenMinutes,
+    /// One hour, `1h`.
+    Hour,
+    /// Four hours, `4h`.
+    FourHours,
+    /// One day, `1d`.
+    Day,
+}
+
+impl Granularity {
+    /// Every granularity, from the finest to the coarsest.
+    pub const ALL: [Granularity; 6] = [
+        Granularity::Minute,
+        Granularity::FiveMinutes,
+        Granularity::FifteenMinutes,
+        Granularity::Hour,
+        Granularity::FourHours,
+        Granularity::Day,
+    ];
+
+    /// Returns the value used for the `granularity` query parameter.
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Granularity::Minute => "1m",
+            Granularity::FiveMinutes => "5m",
+            Granularity::FifteenMinutes => "15m",
+            Granularity::Hour => "1h",
+            Granularity::FourHours => "4h",
+            Granularity::Day => "1d",
+        }
+    }
+
+    /// Returns the length of one interval.
+    #[must_use]
+    pub fn duration(self) -> chrono::Duration {
+        match self {
+            Granularity::Minute => chrono::Duration::minutes(1),
+            Granularity::FiveMinutes => chrono::Duration::minutes(5),
+            Granularity::FifteenMinutes => chrono::Duration::minutes(15),
+            Granularity::Hour => chrono::Duration::hours(1),
+            Granularity::FourHours => chrono::Duration::hours(4),
+            Granularity::Day => chrono::Duration::days(1),
+        }
+    }
+
+    /// Returns the time spanned by `intervals` intervals.
+    fn range(self, intervals: u32) -> chrono::Duration {
+        self.duration() * i32::try_from(intervals).unwrap_or(i32::MAX)
+    }
+}
+
+impl fmt::Display for Granularity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Granularity {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Granularity::ALL
+            .into_iter()
+            .find(|granularity| granularity.as_str() == s.trim())
+            .ok_or_else(|| Error::InvalidArgument {
+                message: format!(
+                    "unknown granularity \"{s}\", expected one of {}",
+                    Granularity::ALL.map(Granularity::as_str).join(", ")
+                ),
+            })
+    }
+}
+
+/// Checks a historical prices request before it is sent, so that mistakes
+/// surface as a precise [`Error::InvalidArgument`] rather than a bad request.
+fn validate_historical_request(
+    pairs: &[TradingPair],
+    start: Option<DateTime<Utc>>,
+    end: Option<DateTime<Utc>>,
+    granularity: Option<Granularity>,
+    max_intervals: Option<u32>,
+) -> Result<()> {
+    let invalid = |message: String| Err(Error::InvalidArgument { message });
+    if pairs.is_empty() {
+        return invalid("at least one trading pair is required".to_string());
+    }
+    let now = Utc::now();
+    if let Some(start) = start {
+        if start > now {
+            return invalid(format!("start {start} is in the future"));
+        }
+    }
+    if let (Some(start), Some(end)) = (start, end) {
+        if start >= end {
+            return invalid(format!("start {start} must be before end {end}"));
+        }
+    }
+    if let (Some(start), Some(granularity), Some(max_intervals)) =
+        (start, granularity, max_intervals)
+    {
+        let range = end.unwrap_or(now) - start;
+        let max_range = granularity.range(max_intervals);
+        if range > max_range {
+            return invalid(format!(
+                "range of {} exceeds the maximum of {} for {granularity} prices",
+                format_duration(range),
+                format_duration(max_range),
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Formats `duration` in its largest whole unit, e.g. `3 days` or `90 minutes`.
+fn format_duration(duration: chrono::Duration) -> String {
+    let seconds = duration.num_seconds();
+    let (count, unit) = [(86_400, "day"), (3_600, "hour"), (60, "minute")]
+        .into_iter()
+        .find(|(unit, _)| seconds % unit == 0)
+        .map_or((seconds, "second"), |(unit, name)| (seconds / unit, name));
+    if count == 1 {
+        format!("1 {unit}")
+    } else {
+        format!("{count} {unit}s")
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
 pub struct HistoricalPriceData {
     pub timestamp: DateTime<Utc>,
     pub price: f64,
@@ -28,7 +190,7 @@ impl fmt::Display for HistoricalPriceData {
     }
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Deserialize)]
 pub struct LatestPriceDetail {
     #[serde(deserialize_with = "string_to_float_map")]
     pub prices: HashMap<String, f64>,
@@ -67,11 +229,31 @@ impl fmt::Display for LatestPriceDetail {
     }
 }
 
+/// A change in the latest prices of a trading pair, yielded by
+/// [`PricesClient::subscribe_prices`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct PriceTick {
+    pub pair: TradingPair,
+    /// When the poll that observed the change completed.
+    pub received_at: DateTime<Utc>,
+    pub detail: LatestPriceDetail,
+}
+
+impl fmt::Display for PriceTick {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Pair: {}, Received At: {}, Details: [{}]",
+            self.pair, self.received_at, self.detail
+        )
+    }
+}
+
 #[derive(Debug, Deserialize)]
 pub struct LatestPricesResponse {
     // This assumes each key in the JSON (like "BTC-USDC", "LTC-USDT") is dynamic and represents a currency pair
     #[serde(flatten)]
-    pub prices: HashMap<String, LatestPriceDetail>,
+    pub prices: HashMap<TradingPair, LatestPriceDetail>,
 }
 
 impl fmt::Display for LatestPricesResponse {
@@ -86,18 +268,205 @@ impl fmt::Display for LatestPricesResponse {
     }
 }
 
-fn string_to_float_map<'de, D>(deserializer: D) -> Result<HashMap<String, f64>, D::Error>
+impl LatestPricesResponse {
+    /// Converts the latest prices to a record batch with the columns of
+    /// [`prices_schema`], one row per pair in pair order.
+    ///
+    /// `price` holds the mean price, `high` and `low` the maximum and minimum
+    /// prices, while `timestamp`, `open` and `close` are null. The prices of
+    /// individual exchanges are not included.
+    pub fn to_record_batch(&self) -> Result<RecordBatch> {
+        let mut pairs: Vec<_> = self.prices.iter().collect();
+        pairs.sort_by_key(|(pair, _)| *pair);
+        let rows: Vec<PriceRow> = pairs
+            .into_iter()
+            .map(|(pair, detail)| PriceRow {
+                pair: pair.clone(),
+                timestamp: None,
+                price: detail.mean_price,
+                open: None,
+                high: detail.max_price,
+                low: detail.min_price,
+                close: None,
+            })
+            .collect();
+        price_rows_to_record_batch(&rows)
+    }
+
+    /// Reads latest prices from a record batch with the columns of
+    /// [`prices_schema`], the inverse of
+    /// [`to_record_batch`](Self::to_record_batch). The prices of individual
+    /// exchanges are left empty.
+    pub fn from_record_batch(batch: &RecordBatch) -> Result<Self> {
+        let prices = from_record_batch::<PriceRow>(batch)?
+            .into_iter()
+            .map(|row| {
+                let detail = LatestPriceDetail {
+                    prices: HashMap::new(),
+                    min_price: row.low,
+                    max_price: row.high,
+                    mean_price: row.price,
+                };
+                (row.pair, detail)
+            })
+            .collect();
+        Ok(Self { prices })
+    }
+}
+
+/// Timezone of price timestamps. An offset rather than `"UTC"`, which the
+/// Arrow CSV and JSON writers only accept with the `chrono-tz` feature.
+const UTC_OFFSET: &str = "+00:00";
+
+/// A row of a prices record batch.
+#[derive(Deserialize)]
+struct PriceRow {
+    pair: TradingPair,
+    timestamp: Option<DateTime<Utc>>,
+    price: Option<f64>,
+    open: Option<f64>,
+    high: Option<f64>,
+    low: Option<f64>,
+    close: Option<f64>,
+}
+
+/// Returns the schema of record batches holding prices, shared by
+/// historical and latest prices: `pair` (`Utf8`), `timestamp` (millisecond
+/// `Timestamp` at offset `+00:00`), then `price`, `open`, `high`, `low` and
+/// `close` (`Float64`). Every column but `pair` is nullable.
+#[must_use]
+pub fn prices_schema() -> SchemaRef {
+    let price = |name| Field::new(name, DataType::Float64, true);
+    Arc::new(Schema::new(vec![
+        Field::new("pair", DataType::Utf8, false),
+        Field::new(
+            "timestamp",
+            DataType::Timestamp(TimeUnit::Millisecond, Some(UTC_OFFSET.into())),
+            true,
+        ),
+        price("price"),
+        price("open"),
+        price("high"),
+        price("low"),
+        price("close"),
+    ]))
+}
+
+fn price_rows_to_record_batch(rows: &[PriceRow]) -> Result<RecordBatch> {
+    let prices = |value: fn(&PriceRow) -> Option<f64>| {
+        Arc::new(rows.iter().map(value).collect::<Float64Array>()) as ArrayRef
+    };
+    let columns = vec![
+        Arc::new(
+            rows.iter()
+                .map(|row| Some(row.pair.to_string()))
+                .collect::<StringArray>(),
+        ) as ArrayRef,
+        Arc::new(
+            rows.iter()
+                .map(|row| row.timestamp.map(|t| t.timestamp_millis()))
+                .collect::<TimestampMillisecondArray>()
+                .with_timezone(UTC_OFFSET),
+        ),
+        prices(|row| row.price),
+        prices(|row| row.open),
+        prices(|row| row.high),
+        prices(|row| row.low),
+        prices(|row| row.close),
+    ];
+    Ok(RecordBatch::try_new(prices_schema(), columns)?)
+}
+
+/// Converts historical prices to a record batch with the columns of
+/// [`prices_schema`], so that they can be exported or joined like query
+/// results. Rows are ordered by pair, then as returned for each pair, and
+/// timestamps are truncated to milliseconds.
+/// ```
+/// use spiceai::{historical_prices_from_record_batch, historical_prices_to_record_batch};
+/// use spiceai::{HistoricalPriceData, TradingPair};
+/// use std::collections::HashMap;
+///
+/// let pair: TradingPair = "BTC-USD".parse().unwrap();
+/// let prices = HashMap::from([(
+///     pair.clone(),
+///     vec![HistoricalPriceData {
+///         timestamp: "2024-01-01T00:00:00Z".parse().unwrap(),
+///         price: 42000.5,
+///         high: None,
+///         low: None,
+///         open: None,
+///         close: None,
+///     }],
+/// )]);
+/// let batch = historical_prices_to_record_batch(&prices).unwrap();
+/// assert_eq!(batch.num_rows(), 1);
+/// assert_eq!(historical_prices_from_record_batch(&batch).unwrap(), prices);
+/// ```
+pub fn historical_prices_to_record_batch(
+    prices: &HashMap<TradingPair, Vec<HistoricalPriceData>>,
+) -> Result<RecordBatch> {
+    let mut pairs: Vec<_> = prices.iter().collect();
+    pairs.sort_by_key(|(pair, _)| *pair);
+    let rows: Vec<PriceRow> = pairs
+        .into_iter()
+        .flat_map(|(pair, data)| {
+            data.iter().map(|price| PriceRow {
+                pair: pair.clone(),
+                timestamp: Some(price.timestamp),
+                price: Some(price.price),
+                open: price.open,
+                high: price.high,
+                low: price.low,
+                close: price.close,
+            })
+        })
+        .collect();
+    price_rows_to_record_batch(&rows)
+}
+
+/// Reads historical prices from a record batch with the columns of
+/// [`prices_schema`], the inverse of [`historical_prices_to_record_batch`].
+/// Columns are matched by name, so the batch may also be a query result;
+/// `timestamp` and `price` must not be null.
+pub fn historical_prices_from_record_batch(
+    batch: &RecordBatch,
+) -> Result<HashMap<TradingPair, Vec<HistoricalPriceData>>> {
+    let mut prices: HashMap<TradingPair, Vec<HistoricalPriceData>> = HashMap::new();
+    for row in from_record_batch::<PriceRow>(batch)? {
+        let (Some(timestamp), Some(price)) = (row.timestamp, row.price) else {
+            return Err(Error::InvalidArgument {
+                message: format!("historical price of {} has no timestamp or price", row.pair),
+            });
+        };
+        prices
+            .entry(row.pair)
+            .or_default()
+            .push(HistoricalPriceData {
+                timestamp,
+                price,
+                high: row.high,
+                low: row.low,
+                open: row.open,
+                close: row.close,
+            });
+    }
+    Ok(prices)
+}
+
+fn string_to_float_map<'de, D>(
+    deserializer: D,
+) -> std::result::Result<HashMap<String, f64>, D::Error>
 where
     D: serde::Deserializer<'de>,
 {
     let map = HashMap::<String, String>::deserialize(deserializer)?;
     map.into_iter()
         .map(|(k, v)| v.parse::<f64>().map(|v_f64| (k, v_f64)))
-        .collect::<Result<HashMap<String, f64>, std::num::ParseFloatError>>()
+        .collect::<std::result::Result<HashMap<String, f64>, std::num::ParseFloatError>>()
         .map_err(serde::de::Error::custom)
 }
 
-fn string_to_float_option<'de, D>(deserializer: D) -> Result<Option<f64>, D::Error>
+fn string_to_float_option<'de, D>(deserializer: D) -> std::result::Result<Option<f64>, D::Error>
 where
     D: serde::Deserializer<'de>,
 {
@@ -107,92 +476,630 @@ where
         .map_err(|x| serde::de::Error::custom(x.to_string()))
 }
 
-async fn map_reqwest_response<T: DeserializeOwned>(resp: Response) -> Result<T, Box<dyn Error>> {
+async fn map_reqwest_response<T: DeserializeOwned>(resp: Response) -> Result<T> {
     match resp.status() {
         StatusCode::OK => {
             let response: T = resp.json().await?;
             Ok(response)
         }
-        StatusCode::BAD_REQUEST => Err("Bad request".into()),
-        StatusCode::TOO_MANY_REQUESTS => Err("Rate limit exceeded, slow down".into()),
-        StatusCode::INTERNAL_SERVER_ERROR => Err("Internal server error".into()),
-        _ => Err(format!("Unexpected response status: {}", resp.status()).into()),
+        StatusCode::BAD_REQUEST => Err(Error::BadRequest {
+            message: response_message(resp).await,
+        }),
+        StatusCode::TOO_MANY_REQUESTS => {
+            let retry_after = resp
+                .headers()
+                .get(RETRY_AFTER)
+                .and_then(|v| v.to_str().ok())
+                .and_then(parse_retry_after);
+            Err(Error::RateLimited { retry_after })
+        }
+        status => Err(Error::Http {
+            status,
+            message: response_message(resp).await,
+        }),
+    }
+}
+
+/// Parses a `Retry-After` header given either in seconds or as an HTTP date.
+/// Dates in the past mean the request can be retried right away.
+fn parse_retry_after(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
     }
+    let date = DateTime::parse_from_rfc2822(value).ok()?;
+    Some(
+        (date.with_timezone(&Utc) - Utc::now())
+            .to_std()
+            .unwrap_or(Duration::ZERO),
+    )
 }
 
+/// Reads the body of an error response, falling back to the status reason.
+async fn response_message(resp: Response) -> String {
+    let status = resp.status();
+    match resp.text().await {
+        Ok(body) if !body.trim().is_empty() => body.trim().to_string(),
+        _ => status
+            .canonical_reason()
+            .unwrap_or("Unknown error")
+            .to_string(),
+    }
+}
+
+/// Supported pairs cached by a [`PricesClient`], with when they were fetched.
+type SupportedPairsCache = Arc<tokio::sync::Mutex<Option<(Instant, Arc<HashSet<TradingPair>>)>>>;
+
+fn join_pairs(pairs: &[TradingPair]) -> String {
+    pairs
+        .iter()
+        .map(TradingPair::to_string)
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+#[derive(Clone)]
 pub struct PricesClient {
     base_url: String,
-    _api_key: String,
+    api_key: Option<String>,
     client: reqwest::Client,
+    retry_policy: RetryPolicy,
+    concurrency: usize,
+    max_intervals: Option<u32>,
+    pair_validation: Option<Duration>,
+    supported_pairs: SupportedPairsCache,
+    #[cfg(feature = "test-util")]
+    cassette: Option<Cassette>,
 }
 
 impl PricesClient {
-    pub fn new(base_url: Option<String>, api_key: String) -> Self {
+    pub fn new(
+        base_url: Option<String>,
+        api_key: Option<String>,
+        options: &ChannelOptions,
+    ) -> Result<Self> {
         let default_url = HTTPS_ADDR.to_string();
-        let client = reqwest::Client::new();
-        PricesClient {
-            base_url: base_url.unwrap_or(default_url),
-            _api_key: api_key,
-            client,
+        let mut builder = reqwest::Client::builder();
+        if let Some(connect_timeout) = options.connect_timeout {
+            builder = builder.connect_timeout(connect_timeout);
         }
+        if let Some(timeout) = options.timeout {
+            builder = builder.timeout(timeout);
+        }
+        let builder = http_tls_config(builder, &options.tls)?;
+        Ok(PricesClient {
+            base_url: base_url.unwrap_or(default_url),
+            api_key,
+            retry_policy: RetryPolicy::default(),
+            concurrency: 4,
+            max_intervals: None,
+            pair_validation: None,
+            supported_pairs: SupportedPairsCache::default(),
+            #[cfg(feature = "test-util")]
+            cassette: None,
+            client: builder
+                .build()
+                .map_err(|e| Error::config_with_source("Cannot build HTTP client", e))?,
+        })
+    }
+
+    /// Sets the policy used to retry transient failures.
+    #[must_use]
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    /// Sets how many requests [`stream_historical_prices`](Self::stream_historical_prices)
+    /// sends in parallel.
+    #[must_use]
+    pub fn with_concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    /// Rejects historical prices requests spanning more than `max_intervals`
+    /// intervals, and fetches streamed ranges in windows of that size.
+    #[must_use]
+    pub fn with_max_intervals(mut self, max_intervals: u32) -> Self {
+        self.max_intervals = Some(max_intervals.max(1));
+        self
+    }
+
+    /// Checks the pairs of every prices request against the supported pairs,
+    /// which are cached for `cache_ttl`.
+    #[must_use]
+    pub fn with_pair_validation(mut self, cache_ttl: Duration) -> Self {
+        self.pair_validation = Some(cache_ttl);
+        self
+    }
+
+    /// Records responses to `cassette`, or serves them from it when it is
+    /// replaying.
+    #[cfg(feature = "test-util")]
+    #[must_use]
+    pub fn with_cassette(mut self, cassette: Cassette) -> Self {
+        self.cassette = Some(cassette);
+        self
     }
 
     fn add_headers(&self, request_builder: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        let request_builder = match &self.api_key {
+            Some(api_key) => request_builder.header("X-API-Key", api_key),
+            None => request_builder,
+        };
         request_builder
-            .header("X-API-Key", &self._api_key)
             .header("Accept", "application/json")
             .header("User-Agent", "spice-rs 1.0")
     }
 
-    pub async fn get_supported_pairs(&self) -> Result<Vec<String>, Box<dyn Error>> {
-        let url = format!("{}/v1/prices/pairs", self.base_url);
-        let request = self.client.get(&url);
-        let response: Vec<String> = self.add_headers(request).send().await?.json().await?;
-        Ok(response)
+    /// Sends a GET request for `path` with the URL-encoded `query`, retrying
+    /// transient failures. Each attempt times out at `deadline`.
+    async fn get_json<T: DeserializeOwned>(
+        &self,
+        operation: &'static str,
+        path: &str,
+        query: &[(&str, String)],
+        deadline: Option<Instant>,
+    ) -> Result<T> {
+        let url = format!("{}{path}", self.base_url);
+        self.retry_policy
+            .retry(operation, || async {
+                let mut request = self.add_headers(self.client.get(&url).query(query));
+                if let Some(deadline) = deadline {
+                    request = request.timeout(remaining(deadline)?);
+                }
+                let request = request.build()?;
+                #[cfg(feature = "test-util")]
+                if let Some(cassette) = &self.cassette {
+                    let response = cassette.send(&self.client, request).await?;
+                    return map_reqwest_response(response).await;
+                }
+                let response = self.client.execute(request).await?;
+                map_reqwest_response(response).await
+            })
+            .await
+    }
+
+    pub async fn get_supported_pairs(&self) -> Result<Vec<TradingPair>> {
+        self.get_supported_pairs_with_options(&QueryOptions::default())
+            .await
     }
 
-    pub async fn get_prices(&self, pairs: &[&str]) -> Result<LatestPricesResponse, Box<dyn Error>> {
-        let url = format!("{}/v1/prices?pairs={}", self.base_url, pairs.join(","));
+    /// Gets the supported pairs, bounded by the timeout and cancellation token
+    /// of `options`.
+    pub async fn get_supported_pairs_with_options(
+        &self,
+        options: &QueryOptions,
+    ) -> Result<Vec<TradingPair>> {
+        let deadline = options.deadline();
+        bounded(
+            self.fetch_supported_pairs(deadline),
+            deadline,
+            options.cancellation_token.as_ref(),
+        )
+        .await
+    }
 
-        self.add_headers(self.client.get(&url))
-            .send()
+    async fn fetch_supported_pairs(&self, deadline: Option<Instant>) -> Result<Vec<TradingPair>> {
+        self.get_json("get_supported_pairs", "/v1/prices/pairs", &[], deadline)
             .await
-            .map(map_reqwest_response)?
+    }
+
+    /// Returns the supported pairs, fetching them again once the cached ones
+    /// are older than `cache_ttl`.
+    async fn supported_pairs(&self, cache_ttl: Duration) -> Result<Arc<HashSet<TradingPair>>> {
+        let mut cache = self.supported_pairs.lock().await;
+        if let Some((fetched_at, pairs)) = cache.as_ref() {
+            if fetched_at.elapsed() < cache_ttl {
+                return Ok(pairs.clone());
+            }
+        }
+        let pairs: Arc<HashSet<_>> = Arc::new(
+            self.fetch_supported_pairs(None)
+                .await?
+                .into_iter()
+                .collect(),
+        );
+        *cache = Some((Instant::now(), pairs.clone()));
+        Ok(pairs)
+    }
+
+    /// Fails with [`Error::InvalidArgument`] naming every pair of `pairs`
+    /// that is not supported. The supported pairs are cached as configured
+    /// by [`with_pair_validation`](Self::with_pair_validation), and fetched
+    /// on every call otherwise.
+    pub async fn validate_pairs(&self, pairs: &[TradingPair]) -> Result<()> {
+        let supported = self
+            .supported_pairs(self.pair_validation.unwrap_or_default())
+            .await?;
+        let unsupported: Vec<String> = pairs
+            .iter()
+            .filter(|pair| !supported.contains(*pair))
+            .map(TradingPair::to_string)
+            .collect();
+        if unsupported.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::InvalidArgument {
+                message: format!("unsupported trading pairs: {}", unsupported.join(", ")),
+            })
+        }
+    }
+
+    /// Validates `pairs` if pair validation is enabled.
+    async fn check_pairs(&self, pairs: &[TradingPair]) -> Result<()> {
+        match self.pair_validation {
+            Some(_) => self.validate_pairs(pairs).await,
+            None => Ok(()),
+        }
+    }
+
+    pub async fn get_prices(&self, pairs: &[TradingPair]) -> Result<LatestPricesResponse> {
+        self.get_prices_with_options(pairs, &QueryOptions::default())
             .await
     }
 
+    /// Gets the latest prices of `pairs`, bounded by the timeout and
+    /// cancellation token of `options`.
+    pub async fn get_prices_with_options(
+        &self,
+        pairs: &[TradingPair],
+        options: &QueryOptions,
+    ) -> Result<LatestPricesResponse> {
+        if pairs.is_empty() {
+            return Err(Error::InvalidArgument {
+                message: "at least one trading pair is required".to_string(),
+            });
+        }
+        let deadline = options.deadline();
+        let request = async {
+            self.check_pairs(pairs).await?;
+            self.fetch_prices(pairs, deadline).await
+        };
+        bounded(request, deadline, options.cancellation_token.as_ref()).await
+    }
+
+    async fn fetch_prices(
+        &self,
+        pairs: &[TradingPair],
+        deadline: Option<Instant>,
+    ) -> Result<LatestPricesResponse> {
+        self.get_json(
+            "get_prices",
+            "/v1/prices",
+            &[("pairs", join_pairs(pairs))],
+            deadline,
+        )
+        .await
+    }
+
+    /// Polls the latest prices of `pairs` every `interval`, yielding a
+    /// [`PriceTick`] for each pair whose prices changed since the previous
+    /// poll. The first poll yields every pair.
+    ///
+    /// Polls that are rate limited are retried after the advertised
+    /// `Retry-After`, or after an exponential backoff, without yielding an
+    /// error. Other failed polls yield their error and polling continues,
+    /// unless the pairs are unsupported. Polling stops when the stream is
+    /// dropped.
+    pub fn subscribe_prices(
+        &self,
+        pairs: &[TradingPair],
+        interval: Duration,
+    ) -> Result<BoxStream<'static, Result<PriceTick>>> {
+        self.subscribe_prices_with_options(pairs, interval, &QueryOptions::default())
+    }
+
+    /// Subscribes to the latest prices of `pairs` like
+    /// [`subscribe_prices`](Self::subscribe_prices). The timeout of `options`
+    /// bounds each poll, and a poll that times out yields [`Error::Timeout`]
+    /// before polling continues. Cancelling the token ends the stream with
+    /// [`Error::Cancelled`].
+    pub fn subscribe_prices_with_options(
+        &self,
+        pairs: &[TradingPair],
+        interval: Duration,
+        options: &QueryOptions,
+    ) -> Result<BoxStream<'static, Result<PriceTick>>> {
+        if pairs.is_empty() {
+            return Err(Error::InvalidArgument {
+                message: "at least one trading pair is required".to_string(),
+            });
+        }
+        if interval.is_zero() {
+            return Err(Error::InvalidArgument {
+                message: "the polling interval must be positive".to_string(),
+            });
+        }
+        let subscription = Subscription {
+            client: self.clone(),
+            pairs: pairs.to_vec(),
+            interval,
+            timeout: options.timeout,
+            backoff: interval,
+            next_poll: Instant::now(),
+            validated: false,
+            finished: false,
+            latest: HashMap::new(),
+            pending: VecDeque::new(),
+        };
+        let ticks = stream::unfold(subscription, |mut subscription| async move {
+            let tick = subscription.next().await?;
+            Some((tick, subscription))
+        })
+        .boxed();
+        Ok(bounded_stream(
+            ticks,
+            None,
+            options.cancellation_token.clone(),
+        ))
+    }
+
     pub async fn get_historical_prices(
         &self,
-        pairs: &[&str],
+        pairs: &[TradingPair],
         start: Option<DateTime<Utc>>,
         end: Option<DateTime<Utc>>,
-        granularity: Option<&str>,
-    ) -> Result<HashMap<String, Vec<HistoricalPriceData>>, Box<dyn Error>> {
-        let mut url = format!(
-            "{}/v1/prices/historical?pairs={}",
-            self.base_url,
-            pairs.join(",")
-        );
+        granularity: Option<Granularity>,
+    ) -> Result<HashMap<TradingPair, Vec<HistoricalPriceData>>> {
+        self.get_historical_prices_with_options(
+            pairs,
+            start,
+            end,
+            granularity,
+            &QueryOptions::default(),
+        )
+        .await
+    }
 
-        if let Some(start_time) = start {
-            let timestamp = start_time.timestamp();
-            url.push_str(&format!("&start={}", timestamp));
+    /// Gets historical prices like
+    /// [`get_historical_prices`](Self::get_historical_prices), bounded by the
+    /// timeout and cancellation token of `options`.
+    pub async fn get_historical_prices_with_options(
+        &self,
+        pairs: &[TradingPair],
+        start: Option<DateTime<Utc>>,
+        end: Option<DateTime<Utc>>,
+        granularity: Option<Granularity>,
+        options: &QueryOptions,
+    ) -> Result<HashMap<TradingPair, Vec<HistoricalPriceData>>> {
+        validate_historical_request(pairs, start, end, granularity, self.max_intervals)?;
+        let deadline = options.deadline();
+        let request = async {
+            self.check_pairs(pairs).await?;
+            self.fetch_historical_prices(pairs, start, end, granularity, deadline)
+                .await
+        };
+        bounded(request, deadline, options.cancellation_token.as_ref()).await
+    }
+
+    async fn fetch_historical_prices(
+        &self,
+        pairs: &[TradingPair],
+        start: Option<DateTime<Utc>>,
+        end: Option<DateTime<Utc>>,
+        granularity: Option<Granularity>,
+        deadline: Option<Instant>,
+    ) -> Result<HashMap<TradingPair, Vec<HistoricalPriceData>>> {
+        let mut query = vec![("pairs", join_pairs(pairs))];
+        if let Some(start) = start {
+            query.push(("start", start.timestamp().to_string()));
         }
+        if let Some(end) = end {
+            query.push(("end", end.timestamp().to_string()));
+        }
+        if let Some(granularity) = granularity {
+            query.push(("granularity", granularity.to_string()));
+        }
+        self.get_json(
+            "get_historical_prices",
+            "/v1/prices/historical",
+            &query,
+            deadline,
+        )
+        .await
+    }
 
-        if let Some(end_time) = end {
-            let timestamp = end_time.timestamp();
-            url.push_str(&format!("&end={}", timestamp));
+    /// Streams historical prices over `[start, end)` of any length.
+    ///
+    /// The range is split into windows of the maximum number of intervals set
+    /// with [`with_max_intervals`](Self::with_max_intervals), or of 1,440
+    /// intervals, which are requested with bounded concurrency. Prices are
+    /// yielded in timestamp order, then by pair, and timestamps returned by
+    /// more than one window are yielded once.
+    pub fn stream_historical_prices(
+        &self,
+        pairs: &[TradingPair],
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+        granularity: Granularity,
+    ) -> Result<BoxStream<'static, Result<(TradingPair, HistoricalPriceData)>>> {
+        self.stream_historical_prices_with_options(
+            pairs,
+            start,
+            end,
+            granularity,
+            &QueryOptions::default(),
+        )
+    }
+
+    /// Streams historical prices like
+    /// [`stream_historical_prices`](Self::stream_historical_prices). The
+    /// timeout of `options` bounds the whole stream, and the stream ends with
+    /// [`Error::Timeout`] or [`Error::Cancelled`] once it passes or the token
+    /// is cancelled.
+    pub fn stream_historical_prices_with_options(
+        &self,
+        pairs: &[TradingPair],
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+        granularity: Granularity,
+        options: &QueryOptions,
+    ) -> Result<BoxStream<'static, Result<(TradingPair, HistoricalPriceData)>>> {
+        validate_historical_request(pairs, Some(start), Some(end), None, self.max_intervals)?;
+        let deadline = options.deadline();
+        let pairs = pairs.to_vec();
+        let client = self.clone();
+        let pages = async move {
+            client.check_pairs(&pairs).await?;
+            let concurrency = client.concurrency;
+            let window =
+                granularity.range(client.max_intervals.unwrap_or(HISTORICAL_WINDOW_INTERVALS));
+            let pages = stream::iter(historical_windows(start, end, window))
+                .map(move |(window_start, window_end)| {
+                    let client = client.clone();
+                    let pairs = pairs.clone();
+                    async move {
+                        let window = client.fetch_historical_prices(
+                            &pairs,
+                            Some(window_start),
+                            Some(window_end),
+                            Some(granularity),
+                            deadline,
+                        );
+                        bounded(window, deadline, None).await
+                    }
+                })
+                .buffered(concurrency);
+            Ok::<_, Error>(pages)
+        };
+
+        let mut latest: HashMap<TradingPair, DateTime<Utc>> = HashMap::new();
+        let prices = stream::once(pages).try_flatten().map_ok(move |page| {
+            let mut prices: Vec<(TradingPair, HistoricalPriceData)> = page
+                .into_iter()
+                .flat_map(|(pair, data)| data.into_iter().map(move |price| (pair.clone(), price)))
+                .filter(|(_, price)| price.timestamp >= start && price.timestamp < end)
+                .collect();
+            prices.sort_by(|(a_pair, a), (b_pair, b)| {
+                a.timestamp
+                    .cmp(&b.timestamp)
+                    .then_with(|| a_pair.cmp(b_pair))
+            });
+            // Windows overlap at their bounds; keep the first price seen.
+            prices.retain(|(pair, price)| {
+                let is_new = match latest.get(pair) {
+                    Some(seen) => price.timestamp > *seen,
+                    None => true,
+                };
+                if is_new {
+                    latest.insert(pair.clone(), price.timestamp);
+                }
+                is_new
+            });
+            stream::iter(prices.into_iter().map(Ok))
+        });
+        Ok(bounded_stream(
+            prices.try_flatten().boxed(),
+            deadline,
+            options.cancellation_token.clone(),
+        ))
+    }
+}
+
+/// The state of a stream returned by [`PricesClient::subscribe_prices`].
+struct Subscription {
+    client: PricesClient,
+    pairs: Vec<TradingPair>,
+    interval: Duration,
+    /// Bounds each poll.
+    timeout: Option<Duration>,
+    /// Delay before polling again after a rate limited poll without a
+    /// `Retry-After`.
+    backoff: Duration,
+    next_poll: Instant,
+    validated: bool,
+    finished: bool,
+    latest: HashMap<TradingPair, LatestPriceDetail>,
+    pending: VecDeque<PriceTick>,
+}
+
+impl Subscription {
+    /// Polls until a price changes or a poll fails. Returns `None` once the
+    /// subscription cannot continue.
+    async fn next(&mut self) -> Option<Result<PriceTick>> {
+        loop {
+            if let Some(tick) = self.pending.pop_front() {
+                return Some(Ok(tick));
+            }
+            if self.finished {
+                return None;
+            }
+            tokio::time::sleep_until(self.next_poll).await;
+            self.next_poll = Instant::now() + self.interval;
+
+            if !self.validated {
+                match self.client.check_pairs(&self.pairs).await {
+                    Ok(()) => self.validated = true,
+                    Err(e) => {
+                        // Unsupported pairs will not become supported by
+                        // polling again.
+                        self.finished = matches!(e, Error::InvalidArgument { .. });
+                        return Some(Err(e));
+                    }
+                }
+            }
+
+            let deadline = self.timeout.map(|timeout| Instant::now() + timeout);
+            let poll = bounded(
+                self.client.fetch_prices(&self.pairs, deadline),
+                deadline,
+                None,
+            );
+            match poll.await {
+                Ok(response) => {
+                    self.backoff = self.interval;
+                    self.push_changes(response);
+                }
+                Err(Error::RateLimited { retry_after }) => {
+                    let delay = match retry_after {
+                        Some(retry_after) => retry_after.max(self.interval),
+                        None => {
+                            self.backoff =
+                                (self.backoff * 2).min(MAX_SUBSCRIPTION_BACKOFF.max(self.interval));
+                            self.backoff
+                        }
+                    };
+                    self.next_poll = Instant::now() + delay;
+                }
+                Err(e) => return Some(Err(e)),
+            }
         }
+    }
 
-        if let Some(gran) = granularity {
-            url.push_str(&format!("&granularity={}", gran));
+    /// Queues a tick for every pair of `response` whose prices changed.
+    fn push_changes(&mut self, response: LatestPricesResponse) {
+        let received_at = Utc::now();
+        let mut changed: Vec<_> = response
+            .prices
+            .into_iter()
+            .filter(|(pair, detail)| self.latest.get(pair) != Some(detail))
+            .collect();
+        changed.sort_by(|(a, _), (b, _)| a.cmp(b));
+        for (pair, detail) in changed {
+            self.latest.insert(pair.clone(), detail.clone());
+            self.pending.push_back(PriceTick {
+                pair,
+                received_at,
+                detail,
+            });
         }
+    }
+}
 
-        println!("URL: {}", url);
-        self.add_headers(self.client.get(&url))
-            .send()
-            .await
-            .map(map_reqwest_response)?
-            .await
+/// Splits `[start, end)` into consecutive windows no longer than `window`.
+fn historical_windows(
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    window: chrono::Duration,
+) -> Vec<(DateTime<Utc>, DateTime<Utc>)> {
+    let mut windows = Vec::new();
+    let mut window_start = start;
+    while window_start < end {
+        let window_end = (window_start + window).min(end);
+        windows.push((window_start, window_end));
+        window_start = window_end;
     }
+    windows
 }
@@ -0,0 +1,294 @@
+use std::fmt;
+use std::io::Write;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+
+use arrow::compute::concat_batches;
+use arrow::datatypes::{Schema, SchemaRef};
+use arrow::record_batch::RecordBatch;
+use futures::stream::{self, BoxStream};
+use futures::{Stream, StreamExt, TryStreamExt};
+use serde::de::DeserializeOwned;
+#[cfg(feature = "test-util")]
+use tokio::time::Instant;
+#[cfg(feature = "test-util")]
+use tokio_util::sync::CancellationToken;
+use tonic::metadata::MetadataMap;
+
+use crate::de::from_record_batch;
+use crate::error::{Error, Result};
+use crate::export::{BatchWriter, ExportFormat};
+use crate::flight::RecordBatchStream;
+#[cfg(feature = "test-util")]
+use crate::options::bounded_stream;
+
+/// Response header carrying the id the server assigned to the query.
+pub const QUERY_ID_HEADER: &str = "x-spice-query-id";
+
+/// Trailers collected from every endpoint of a query as their streams end.
+pub(crate) type SharedTrailers = Arc<Mutex<Option<MetadataMap>>>;
+
+/// Batches buffered between a result and the thread writing it to a file.
+const WRITE_BUFFER_BATCHES: usize = 4;
+
+/// The results of a query.
+///
+/// `QueryResult` is a [`Stream`] of record batches covering every endpoint of
+/// the query, and also offers shortcuts for the common ways of consuming it.
+/// ```no_run
+/// # use spiceai::Client;
+/// #
+/// # #[tokio::main]
+/// # async fn main() {
+/// #  let client = Client::new("API_KEY").await.unwrap();
+/// let batch = client
+///     .query("SELECT * FROM eth.recent_blocks LIMIT 10;")
+///     .await
+///     .unwrap()
+///     .concat()
+///     .await
+///     .unwrap();
+/// assert_eq!(batch.num_rows(), 10);
+/// # }
+/// ```
+pub struct QueryResult {
+    inner: RecordBatchStream,
+    schema: Option<SchemaRef>,
+    total_records: Option<u64>,
+    total_bytes: Option<u64>,
+    headers: MetadataMap,
+    trailers: SharedTrailers,
+}
+
+impl QueryResult {
+    pub(crate) fn new(
+        inner: RecordBatchStream,
+        schema: Option<SchemaRef>,
+        total_records: Option<u64>,
+        total_bytes: Option<u64>,
+        headers: MetadataMap,
+        trailers: SharedTrailers,
+    ) -> Self {
+        Self {
+            inner,
+            schema,
+            total_records,
+            total_bytes,
+            headers,
+            trailers,
+        }
+    }
+
+    /// Ends the result with an error once `deadline` passes or `cancel` is
+    /// cancelled.
+    #[cfg(feature = "test-util")]
+    pub(crate) fn bounded(
+        mut self,
+        deadline: Option<Instant>,
+        cancel: Option<CancellationToken>,
+    ) -> Self {
+        self.inner = bounded_stream(self.inner, deadline, cancel);
+        self
+    }
+
+    /// Returns the schema of the result batches, as advertised by the server
+    /// or, failing that, taken from the first batch read.
+    #[must_use]
+    pub fn schema(&self) -> Option<SchemaRef> {
+        self.schema.clone()
+    }
+
+    /// Returns the number of rows in the result, if known to the server.
+    #[must_use]
+    pub fn total_records(&self) -> Option<u64> {
+        self.total_records
+    }
+
+    /// Returns the size of the result in bytes, if known to the server.
+    #[must_use]
+    pub fn total_bytes(&self) -> Option<u64> {
+        self.total_bytes
+    }
+
+    /// Returns the response headers of the first endpoint.
+    #[must_use]
+    pub fn headers(&self) -> &MetadataMap {
+        &self.headers
+    }
+
+    /// Returns the response trailers of the endpoints read so far. Trailers
+    /// are only sent once an endpoint's stream ends, so this is typically
+    /// `None` until the result has been fully read.
+    #[must_use]
+    pub fn trailers(&self) -> Option<MetadataMap> {
+        self.trailers
+            .lock()
+            .expect("trailers lock poisoned")
+            .clone()
+    }
+
+    /// Returns the id the server assigned to the query, read from the
+    /// [`QUERY_ID_HEADER`] response header or trailer.
+    #[must_use]
+    pub fn query_id(&self) -> Option<String> {
+        let from = |md: &MetadataMap| {
+            md.get(QUERY_ID_HEADER)
+                .and_then(|v| v.to_str().ok())
+                .map(str::to_string)
+        };
+        from(&self.headers).or_else(|| self.trailers().as_ref().and_then(from))
+    }
+
+    /// Reads every batch of the result.
+    pub async fn collect_batches(self) -> Result<Vec<RecordBatch>> {
+        self.try_collect().await
+    }
+
+    /// Reads the whole result into a single batch.
+    pub async fn concat(mut self) -> Result<RecordBatch> {
+        let mut batches = Vec::new();
+        while let Some(batch) = self.next().await {
+            batches.push(batch?);
+        }
+        let schema = self.schema.unwrap_or_else(|| Arc::new(Schema::empty()));
+        Ok(concat_batches(&schema, &batches)?)
+    }
+
+    /// Deserializes each row into a `T`, as described in
+    /// [`from_record_batch`](crate::from_record_batch).
+    pub fn into_rows<T: DeserializeOwned + Send + 'static>(self) -> BoxStream<'static, Result<T>> {
+        self.map(|batch| batch.and_then(|batch| from_record_batch::<T>(&batch)))
+            .map_ok(|rows| stream::iter(rows.into_iter().map(Ok)))
+            .try_flatten()
+            .boxed()
+    }
+
+    /// Streams the result into `writer` in the given format, one batch at a
+    /// time, and returns the number of rows written.
+    ///
+    /// Batches are encoded and written on the calling task, which blocks
+    /// while `writer` does. For large exports to slow writers, prefer
+    /// [`Client::query_to_file`](crate::Client::query_to_file), which writes
+    /// on a blocking thread, or call this from
+    /// [`tokio::task::spawn_blocking`].
+    /// ```no_run
+    /// # use spiceai::{Client, ExportFormat};
+    /// #
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// #  let client = Client::new("API_KEY").await.unwrap();
+    /// let result = client
+    ///     .query("SELECT * FROM eth.recent_blocks LIMIT 10;")
+    ///     .await
+    ///     .unwrap();
+    /// let rows = result.write_to(ExportFormat::Csv, std::io::stdout()).await;
+    /// # }
+    /// ```
+    pub async fn write_to<W: Write + Send>(
+        mut self,
+        format: ExportFormat,
+        writer: W,
+    ) -> Result<usize> {
+        // File formats start with the schema, which may only be known once
+        // the first batch has arrived.
+        let first = self.next().await.transpose()?;
+        let schema = self
+            .schema
+            .clone()
+            .unwrap_or_else(|| Arc::new(Schema::empty()));
+        let mut out = BatchWriter::try_new(format, writer, &schema)?;
+        let mut rows = 0;
+        if let Some(batch) = first {
+            rows += batch.num_rows();
+            out.write(&batch)?;
+        }
+        while let Some(batch) = self.next().await {
+            let batch = batch?;
+            rows += batch.num_rows();
+            out.write(&batch)?;
+        }
+        out.finish()?;
+        Ok(rows)
+    }
+
+    /// Streams the result into `writer` like [`write_to`](Self::write_to),
+    /// but encodes and writes the batches on a blocking thread so that the
+    /// runtime keeps serving other tasks.
+    pub(crate) async fn write_to_in_background<W: Write + Send + 'static>(
+        mut self,
+        format: ExportFormat,
+        writer: W,
+    ) -> Result<usize> {
+        let first = self.next().await.transpose()?;
+        let schema = self
+            .schema
+            .clone()
+            .unwrap_or_else(|| Arc::new(Schema::empty()));
+        let (tx, mut rx) = tokio::sync::mpsc::channel::<RecordBatch>(WRITE_BUFFER_BATCHES);
+        let writing = tokio::task::spawn_blocking(move || {
+            let mut out = BatchWriter::try_new(format, writer, &schema)?;
+            let mut rows = 0;
+            while let Some(batch) = rx.blocking_recv() {
+                rows += batch.num_rows();
+                out.write(&batch)?;
+            }
+            out.finish()?;
+            Ok::<_, Error>(rows)
+        });
+
+        let mut read = Ok(());
+        let mut next = first.map(Ok);
+        while let Some(batch) = next {
+            match batch {
+                Ok(batch) => {
+                    // The writer failed; its error is reported below.
+                    if tx.send(batch).await.is_err() {
+                        break;
+                    }
+                }
+                Err(e) => {
+                    read = Err(e);
+                    break;
+                }
+            }
+            next = self.next().await;
+        }
+        drop(tx);
+        let written = writing.await.map_err(Error::export)?;
+        read?;
+        written
+    }
+
+    /// Reads the whole result and returns the number of rows.
+    pub async fn row_count(self) -> Result<usize> {
+        self.try_fold(0, |rows, batch| async move { Ok(rows + batch.num_rows()) })
+            .await
+    }
+}
+
+impl fmt::Debug for QueryResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("QueryResult")
+            .field("schema", &self.schema)
+            .field("total_records", &self.total_records)
+            .field("total_bytes", &self.total_bytes)
+            .field("query_id", &self.query_id())
+            .field("headers", &self.headers)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Stream for QueryResult {
+    type Item = Result<RecordBatch>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let poll = self.inner.poll_next_unpin(cx);
+        if let Poll::Ready(Some(Ok(batch))) = &poll {
+            if self.schema.is_none() {
+                self.schema = Some(batch.schema());
+            }
+        }
+        poll
+    }
+}
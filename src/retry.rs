@@ -0,0 +1,223 @@
+use std::fmt;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+
+use rand::Rng;
+use reqwest::StatusCode;
+
+use crate::error::{Error, Result};
+
+/// Details about a failed attempt that is about to be retried, passed to the
+/// hook registered with [`RetryPolicy::on_retry`].
+#[derive(Debug)]
+pub struct RetryEvent<'a> {
+    /// The operation being retried, e.g. `"handshake"` or `"get_prices"`.
+    pub operation: &'static str,
+    /// The attempt that failed, starting at 1.
+    pub attempt: u32,
+    /// How long the client waits before the next attempt.
+    pub delay: Duration,
+    /// The error returned by the failed attempt.
+    pub error: &'a Error,
+}
+
+type RetryHook = Arc<dyn Fn(&RetryEvent<'_>) + Send + Sync>;
+
+/// Controls how transient failures are retried.
+///
+/// The policy is applied uniformly to the Flight handshake, `get_flight_info`,
+/// `do_get` setup and every prices request. Failed attempts are retried with
+/// exponential backoff when the error is a Flight status in
+/// [`retryable_codes`](Self::with_retryable_codes) or an HTTP status in
+/// [`retryable_statuses`](Self::with_retryable_statuses). A `Retry-After`
+/// advertised by a rate-limited response takes precedence over the backoff,
+/// but is capped by the [maximum backoff](Self::with_max_backoff) too.
+///
+/// By default, up to 3 attempts are made, backing off from 100ms to at most
+/// 5s, retrying `Unavailable` and `ResourceExhausted` Flight statuses and HTTP
+/// 429, 500 and 503 responses.
+/// ```
+/// use spiceai::RetryPolicy;
+/// use std::time::Duration;
+///
+/// let policy = RetryPolicy::default()
+///     .with_max_attempts(5)
+///     .with_initial_backoff(Duration::from_millis(200))
+///     .on_retry(|event| eprintln!("retrying {}: {}", event.operation, event.error));
+/// ```
+#[derive(Clone)]
+pub struct RetryPolicy {
+    max_attempts: u32,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+    multiplier: f64,
+    jitter: bool,
+    retryable_codes: Vec<tonic::Code>,
+    retryable_statuses: Vec<StatusCode>,
+    on_retry: Option<RetryHook>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(5),
+            multiplier: 2.0,
+            jitter: true,
+            retryable_codes: vec![tonic::Code::Unavailable, tonic::Code::ResourceExhausted],
+            retryable_statuses: vec![
+                StatusCode::TOO_MANY_REQUESTS,
+                StatusCode::INTERNAL_SERVER_ERROR,
+                StatusCode::SERVICE_UNAVAILABLE,
+            ],
+            on_retry: None,
+        }
+    }
+}
+
+impl fmt::Debug for RetryPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RetryPolicy")
+            .field("max_attempts", &self.max_attempts)
+            .field("initial_backoff", &self.initial_backoff)
+            .field("max_backoff", &self.max_backoff)
+            .field("multiplier", &self.multiplier)
+            .field("jitter", &self.jitter)
+            .field("retryable_codes", &self.retryable_codes)
+            .field("retryable_statuses", &self.retryable_statuses)
+            .field("on_retry", &self.on_retry.as_ref().map(|_| ".."))
+            .finish()
+    }
+}
+
+impl RetryPolicy {
+    /// A policy that never retries.
+    #[must_use]
+    pub fn none() -> Self {
+        Self::default().with_max_attempts(1)
+    }
+
+    /// Sets the total number of attempts, including the first one.
+    #[must_use]
+    pub fn with_max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = max_attempts.max(1);
+        self
+    }
+
+    /// Sets the delay before the first retry.
+    #[must_use]
+    pub fn with_initial_backoff(mut self, initial_backoff: Duration) -> Self {
+        self.initial_backoff = initial_backoff;
+        self
+    }
+
+    /// Sets the upper bound for the delay between attempts.
+    #[must_use]
+    pub fn with_max_backoff(mut self, max_backoff: Duration) -> Self {
+        self.max_backoff = max_backoff;
+        self
+    }
+
+    /// Sets the factor the delay grows by after each attempt.
+    #[must_use]
+    pub fn with_multiplier(mut self, multiplier: f64) -> Self {
+        self.multiplier = multiplier.max(1.0);
+        self
+    }
+
+    /// Enables or disables randomizing delays, which spreads out retries from
+    /// concurrent clients. Enabled by default.
+    #[must_use]
+    pub fn with_jitter(mut self, jitter: bool) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    /// Sets the Flight status codes that are retried.
+    #[must_use]
+    pub fn with_retryable_codes(mut self, codes: impl IntoIterator<Item = tonic::Code>) -> Self {
+        self.retryable_codes = codes.into_iter().collect();
+        self
+    }
+
+    /// Sets the HTTP statuses that are retried.
+    #[must_use]
+    pub fn with_retryable_statuses(
+        mut self,
+        statuses: impl IntoIterator<Item = StatusCode>,
+    ) -> Self {
+        self.retryable_statuses = statuses.into_iter().collect();
+        self
+    }
+
+    /// Registers a hook called before every retry.
+    #[must_use]
+    pub fn on_retry(mut self, hook: impl Fn(&RetryEvent<'_>) + Send + Sync + 'static) -> Self {
+        self.on_retry = Some(Arc::new(hook));
+        self
+    }
+
+    /// Returns whether `error` is transient according to this policy.
+    #[must_use]
+    pub fn is_retryable(&self, error: &Error) -> bool {
+        match error {
+            Error::Flight { code, .. } => self.retryable_codes.contains(code),
+            Error::RateLimited { .. } => self
+                .retryable_statuses
+                .contains(&StatusCode::TOO_MANY_REQUESTS),
+            Error::Http { status, .. } => self.retryable_statuses.contains(status),
+            _ => false,
+        }
+    }
+
+    fn delay(&self, attempt: u32, error: &Error) -> Duration {
+        if let Error::RateLimited {
+            retry_after: Some(retry_after),
+        } = error
+        {
+            return (*retry_after).min(self.max_backoff);
+        }
+        let exponent = i32::try_from(attempt.saturating_sub(1)).unwrap_or(i32::MAX);
+        let backoff = self
+            .initial_backoff
+            .mul_f64(self.multiplier.powi(exponent))
+            .min(self.max_backoff);
+        if self.jitter {
+            // Equal jitter: wait at least half of the backoff.
+            let half = backoff / 2;
+            half + half.mul_f64(rand::thread_rng().gen::<f64>())
+        } else {
+            backoff
+        }
+    }
+
+    /// Runs `call` until it succeeds, fails with a non-retryable error or the
+    /// attempts are exhausted.
+    pub(crate) async fn retry<T, F, Fut>(&self, operation: &'static str, mut call: F) -> Result<T>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        let mut attempt = 1;
+        loop {
+            match call().await {
+                Err(error) if attempt < self.max_attempts && self.is_retryable(&error) => {
+                    let delay = self.delay(attempt, &error);
+                    if let Some(hook) = &self.on_retry {
+                        hook(&RetryEvent {
+                            operation,
+                            attempt,
+                            delay,
+                            error: &error,
+                        });
+                    }
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+}
@@ -0,0 +1,944 @@
+//! In-process mock Spice servers for testing code that uses this crate
+//! without network access or an API key.
+//!
+//! Enabled by the `test-util` feature. [`MockServer`] runs a Flight service
+//! and an HTTP prices API on local ports, serves the canned record batches
+//! and JSON registered with it, checks the credentials the client sends, and
+//! can inject errors and latency. A [`Cassette`] instead replays traffic
+//! recorded against the real service.
+//! ```
+//! use arrow::array::Int64Array;
+//! use arrow::record_batch::RecordBatch;
+//! use spiceai::testing::MockServer;
+//! use std::sync::Arc;
+//!
+//! # #[tokio::main]
+//! # async fn main() {
+//! let server = MockServer::start().await.unwrap();
+//! let batch =
+//!     RecordBatch::try_from_iter([("number", Arc::new(Int64Array::from(vec![1, 2])) as _)])
+//!         .unwrap();
+//! server.add_query("SELECT number FROM eth.blocks", vec![batch]);
+//!
+//! let client = server.client().await.unwrap();
+//! let rows = client
+//!     .query("SELECT number FROM eth.blocks")
+//!     .await
+//!     .unwrap()
+//!     .row_count()
+//!     .await
+//!     .unwrap();
+//! assert_eq!(rows, 2);
+//! # }
+//! ```
+
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+
+use arrow::datatypes::{Schema, SchemaRef};
+use arrow::ipc::writer::IpcWriteOptions;
+use arrow::record_batch::RecordBatch;
+use arrow_flight::encode::FlightDataEncoderBuilder;
+use arrow_flight::flight_service_server::{FlightService, FlightServiceServer};
+use arrow_flight::sql::{
+    ActionClosePreparedStatementRequest, ActionCreatePreparedStatementRequest,
+    ActionCreatePreparedStatementResult, Any, CommandGetCatalogs, CommandGetDbSchemas,
+    CommandGetTables, CommandPreparedStatementQuery, CommandStatementQuery, ProstMessageExt,
+};
+use arrow_flight::utils::flight_data_to_batches;
+use arrow_flight::{
+    Action, ActionType, Criteria, Empty, FlightData, FlightDescriptor, FlightEndpoint, FlightInfo,
+    HandshakeRequest, HandshakeResponse, IpcMessage, PutResult, SchemaAsIpc, SchemaResult, Ticket,
+};
+use base64::prelude::BASE64_STANDARD;
+use base64::Engine;
+use bytes::Bytes;
+use futures::stream;
+use futures::{StreamExt, TryStreamExt};
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Request as HttpRequestBody, Response as HttpResponseBody};
+use prost::Message;
+use reqwest::StatusCode;
+use serde::Serialize;
+use tokio::task::JoinHandle;
+use tokio_stream::wrappers::TcpListenerStream;
+use tonic::metadata::{MetadataMap, MetadataValue};
+use tonic::transport::{Server, ServerTlsConfig};
+use tonic::{Request, Response, Status, Streaming};
+
+use crate::client::{SpiceClient, SpiceClientBuilder};
+use crate::error::{Error, Result};
+
+pub use crate::cassette::Cassette;
+
+/// The API key accepted by a [`MockServer`].
+pub const MOCK_API_KEY: &str = "mock-app|mock-secret";
+
+/// A Flight RPC served by a [`MockServer`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FlightCall {
+    Handshake,
+    GetFlightInfo,
+    GetSchema,
+    DoGet,
+    DoAction,
+    DoPut,
+}
+
+/// A Flight request received by a [`MockServer`].
+#[derive(Debug, Clone)]
+pub struct FlightRequest {
+    pub call: FlightCall,
+    /// The `authorization` header sent with the request.
+    pub authorization: Option<String>,
+    /// The SQL of `GetFlightInfo`, `GetSchema` and `DoPut` requests, the
+    /// ticket of `DoGet` requests and the action type of `DoAction` requests.
+    /// Flight SQL metadata requests are named after their command, e.g.
+    /// `CommandGetTables`.
+    pub command: Option<String>,
+    /// Whether the descriptor of a `GetFlightInfo` or `GetSchema` request
+    /// held a Flight SQL command rather than raw SQL.
+    pub flight_sql: bool,
+}
+
+/// An HTTP request received by a [`MockServer`].
+#[derive(Debug, Clone)]
+pub struct HttpRequest {
+    pub method: String,
+    pub path: String,
+    pub query: Option<String>,
+    pub headers: HashMap<String, String>,
+}
+
+/// A canned HTTP response served by a [`MockServer`].
+#[derive(Debug, Clone)]
+pub struct MockResponse {
+    status: StatusCode,
+    headers: Vec<(String, String)>,
+    body: String,
+}
+
+impl MockResponse {
+    #[must_use]
+    pub fn new(status: StatusCode) -> Self {
+        Self {
+            status,
+            headers: Vec::new(),
+            body: String::new(),
+        }
+    }
+
+    /// A `200 OK` response with `value` serialized as its JSON body.
+    #[must_use]
+    pub fn json(value: &impl Serialize) -> Self {
+        Self::new(StatusCode::OK)
+            .with_header("content-type", "application/json")
+            .with_body(serde_json::to_string(value).expect("value is not serializable"))
+    }
+
+    #[must_use]
+    pub fn with_header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    #[must_use]
+    pub fn with_body(mut self, body: impl Into<String>) -> Self {
+        self.body = body.into();
+        self
+    }
+}
+
+struct MockQuery {
+    schema: SchemaRef,
+    partitions: Vec<Vec<RecordBatch>>,
+}
+
+/// A query resolved from a descriptor command or a ticket.
+struct Resolved {
+    /// How the query is named in [`FlightRequest::command`].
+    command: String,
+    /// Resolves the query again when sent back in a ticket.
+    key: Bytes,
+    query: Arc<MockQuery>,
+}
+
+struct MockTable {
+    catalog: String,
+    schema: String,
+    name: String,
+    table_type: String,
+    arrow_schema: SchemaRef,
+}
+
+#[derive(Default)]
+struct State {
+    queries: HashMap<String, Arc<MockQuery>>,
+    tables: Vec<MockTable>,
+    parameter_schemas: HashMap<String, SchemaRef>,
+    /// The SQL of each open prepared statement, by handle.
+    prepared_statements: HashMap<Bytes, String>,
+    issued_statements: u64,
+    bound_parameters: Vec<RecordBatch>,
+    tokens: HashSet<String>,
+    issued_tokens: u64,
+    flight_failures: HashMap<FlightCall, VecDeque<Status>>,
+    http_routes: HashMap<String, MockResponse>,
+    http_failures: VecDeque<MockResponse>,
+    response_headers: Vec<(String, String)>,
+    endpoint_location: Option<String>,
+    latency: Duration,
+    flight_requests: Vec<FlightRequest>,
+    http_requests: Vec<HttpRequest>,
+}
+
+type SharedState = Arc<Mutex<State>>;
+
+fn lock(state: &SharedState) -> MutexGuard<'_, State> {
+    state.lock().expect("mock state lock poisoned")
+}
+
+/// A local Flight and HTTP server standing in for Spice.ai. The servers stop
+/// when the `MockServer` is dropped.
+pub struct MockServer {
+    state: SharedState,
+    flight_addr: SocketAddr,
+    flight_tls: bool,
+    http_addr: SocketAddr,
+    tasks: Vec<JoinHandle<()>>,
+}
+
+impl MockServer {
+    /// Starts the servers on free local ports.
+    pub async fn start() -> Result<Self> {
+        Self::start_with_tls(None).await
+    }
+
+    /// Starts the servers with the Flight server accepting only TLS
+    /// connections configured by `tls`, e.g. to test client certificates.
+    /// [`flight_url`](Self::flight_url) then names the server `localhost`.
+    pub async fn start_tls(tls: ServerTlsConfig) -> Result<Self> {
+        Self::start_with_tls(Some(tls)).await
+    }
+
+    async fn start_with_tls(tls: Option<ServerTlsConfig>) -> Result<Self> {
+        let state = SharedState::default();
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+            .await
+            .map_err(|e| Error::config_with_source("Failed to bind mock Flight server", e))?;
+        let flight_addr = listener
+            .local_addr()
+            .map_err(|e| Error::config_with_source("Failed to bind mock Flight server", e))?;
+        let service = FlightServiceServer::new(MockFlightService {
+            state: state.clone(),
+        });
+        let flight_tls = tls.is_some();
+        let mut server = Server::builder();
+        if let Some(tls) = tls {
+            server = server
+                .tls_config(tls)
+                .map_err(|e| Error::config_with_source("Invalid mock server TLS config", e))?;
+        }
+        let flight = tokio::spawn(async move {
+            let _ = server
+                .add_service(service)
+                .serve_with_incoming(TcpListenerStream::new(listener))
+                .await;
+        });
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0")
+            .and_then(|listener| listener.set_nonblocking(true).map(|()| listener))
+            .map_err(|e| Error::config_with_source("Failed to bind mock HTTP server", e))?;
+        let http_addr = listener
+            .local_addr()
+            .map_err(|e| Error::config_with_source("Failed to bind mock HTTP server", e))?;
+        let http_state = state.clone();
+        let make_service = make_service_fn(move |_| {
+            let state = http_state.clone();
+            async move { Ok::<_, Infallible>(service_fn(move |req| serve_http(state.clone(), req))) }
+        });
+        let server = hyper::Server::from_tcp(listener)
+            .map_err(|e| Error::config_with_source("Failed to bind mock HTTP server", e))?
+            .serve(make_service);
+        let http = tokio::spawn(async move {
+            let _ = server.await;
+        });
+
+        Ok(Self {
+            state,
+            flight_addr,
+            flight_tls,
+            http_addr,
+            tasks: vec![flight, http],
+        })
+    }
+
+    /// Returns the URL of the Flight server, which also serves Firecache.
+    #[must_use]
+    pub fn flight_url(&self) -> String {
+        if self.flight_tls {
+            format!("https://localhost:{}", self.flight_addr.port())
+        } else {
+            format!("http://{}", self.flight_addr)
+        }
+    }
+
+    /// Returns the URL of the HTTP prices API.
+    #[must_use]
+    pub fn http_url(&self) -> String {
+        format!("http://{}", self.http_addr)
+    }
+
+    /// Returns a builder for a client pointed at this server with
+    /// [`MOCK_API_KEY`], for tests that need further configuration.
+    #[must_use]
+    pub fn builder(&self) -> SpiceClientBuilder {
+        SpiceClientBuilder::new()
+            .api_key(MOCK_API_KEY)
+            .flight_url(self.flight_url())
+            .firecache_url(self.flight_url())
+            .http_url(self.http_url())
+    }
+
+    /// Builds a client pointed at this server.
+    pub async fn client(&self) -> Result<SpiceClient> {
+        self.builder().build().await
+    }
+
+    /// Serves `batches` from a single endpoint for `sql`.
+    pub fn add_query(&self, sql: impl Into<String>, batches: Vec<RecordBatch>) {
+        self.add_partitioned_query(sql, vec![batches]);
+    }
+
+    /// Serves each partition from its own endpoint for `sql`.
+    pub fn add_partitioned_query(&self, sql: impl Into<String>, partitions: Vec<Vec<RecordBatch>>) {
+        let schema = partitions
+            .iter()
+            .flatten()
+            .next()
+            .map_or_else(|| Arc::new(Schema::empty()), RecordBatch::schema);
+        lock(&self.state)
+            .queries
+            .insert(sql.into(), Arc::new(MockQuery { schema, partitions }));
+    }
+
+    /// Lists a `TABLE` in the Flight SQL catalog, schema and table metadata
+    /// served by this server.
+    pub fn add_table(
+        &self,
+        catalog: impl Into<String>,
+        schema: impl Into<String>,
+        name: impl Into<String>,
+        arrow_schema: SchemaRef,
+    ) {
+        self.add_table_of_type(catalog, schema, name, "TABLE", arrow_schema);
+    }
+
+    /// Lists a table of type `table_type`, e.g. `VIEW`, in the Flight SQL
+    /// metadata served by this server.
+    pub fn add_table_of_type(
+        &self,
+        catalog: impl Into<String>,
+        schema: impl Into<String>,
+        name: impl Into<String>,
+        table_type: impl Into<String>,
+        arrow_schema: SchemaRef,
+    ) {
+        lock(&self.state).tables.push(MockTable {
+            catalog: catalog.into(),
+            schema: schema.into(),
+            name: name.into(),
+            table_type: table_type.into(),
+            arrow_schema,
+        });
+    }
+
+    /// Declares the parameters of statements prepared from `sql`, whose
+    /// parameter schema is empty otherwise. `sql` must also be registered
+    /// with [`add_query`](Self::add_query) to be prepared.
+    pub fn set_parameter_schema(&self, sql: impl Into<String>, schema: SchemaRef) {
+        lock(&self.state)
+            .parameter_schemas
+            .insert(sql.into(), schema);
+    }
+
+    /// Returns the parameters bound to prepared statements with `DoPut`, in
+    /// the order they were received.
+    #[must_use]
+    pub fn bound_parameters(&self) -> Vec<RecordBatch> {
+        lock(&self.state).bound_parameters.clone()
+    }
+
+    /// Returns how many prepared statements have been created and not closed.
+    #[must_use]
+    pub fn open_prepared_statements(&self) -> usize {
+        lock(&self.state).prepared_statements.len()
+    }
+
+    /// Serves `response` for HTTP requests to `path`, whatever their query
+    /// string. Requests without [`MOCK_API_KEY`] in their `X-API-Key`
+    /// header are answered with `401 Unauthorized` instead.
+    pub fn mock_http(&self, path: impl Into<String>, response: MockResponse) {
+        lock(&self.state).http_routes.insert(path.into(), response);
+    }
+
+    /// Fails the next `call` with `status`. Failures queue up when called
+    /// repeatedly.
+    pub fn fail_next(&self, call: FlightCall, status: Status) {
+        lock(&self.state)
+            .flight_failures
+            .entry(call)
+            .or_default()
+            .push_back(status);
+    }
+
+    /// Answers the next HTTP request with `response` instead of its route.
+    pub fn fail_next_http(&self, response: MockResponse) {
+        lock(&self.state).http_failures.push_back(response);
+    }
+
+    /// Delays every response by `latency`.
+    pub fn set_latency(&self, latency: Duration) {
+        lock(&self.state).latency = latency;
+    }
+
+    /// Adds a header to every `DoGet` response.
+    pub fn add_response_header(&self, name: impl Into<String>, value: impl Into<String>) {
+        lock(&self.state)
+            .response_headers
+            .push((name.into(), value.into()));
+    }
+
+    /// Advertises `uri` as the location of every endpoint, so that clients
+    /// fetch results from it instead of the channel they queried, e.g.
+    /// `grpc+tcp://localhost:{port}` to dial this server under another name.
+    pub fn set_endpoint_location(&self, uri: impl Into<String>) {
+        lock(&self.state).endpoint_location = Some(uri.into());
+    }
+
+    /// Invalidates every issued token, so that the next call is rejected as
+    /// `Unauthenticated` until the client handshakes again.
+    pub fn expire_tokens(&self) {
+        lock(&self.state).tokens.clear();
+    }
+
+    /// Returns the Flight requests received so far.
+    #[must_use]
+    pub fn flight_requests(&self) -> Vec<FlightRequest> {
+        lock(&self.state).flight_requests.clone()
+    }
+
+    /// Returns the HTTP requests received so far.
+    #[must_use]
+    pub fn http_requests(&self) -> Vec<HttpRequest> {
+        lock(&self.state).http_requests.clone()
+    }
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        for task in &self.tasks {
+            task.abort();
+        }
+    }
+}
+
+struct MockFlightService {
+    state: SharedState,
+}
+
+// `Status` is what the service answers with, however large.
+#[allow(clippy::result_large_err)]
+impl MockFlightService {
+    /// Records the request, applies the configured latency and failures and,
+    /// for calls other than the handshake, checks the bearer token.
+    async fn enter(
+        &self,
+        call: FlightCall,
+        metadata: &MetadataMap,
+        command: Option<String>,
+        flight_sql: bool,
+    ) -> std::result::Result<(), Status> {
+        let authorization = metadata
+            .get("authorization")
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_string);
+        let latency = {
+            let mut state = lock(&self.state);
+            state.flight_requests.push(FlightRequest {
+                call,
+                authorization: authorization.clone(),
+                command,
+                flight_sql,
+            });
+            state.latency
+        };
+        if !latency.is_zero() {
+            tokio::time::sleep(latency).await;
+        }
+
+        let mut state = lock(&self.state);
+        if let Some(status) = state
+            .flight_failures
+            .get_mut(&call)
+            .and_then(VecDeque::pop_front)
+        {
+            return Err(status);
+        }
+        if call != FlightCall::Handshake {
+            let token = authorization
+                .as_deref()
+                .and_then(|auth| auth.strip_prefix("Bearer "));
+            if !token.is_some_and(|token| state.tokens.contains(token)) {
+                return Err(Status::unauthenticated("Invalid or expired token"));
+            }
+        }
+        Ok(())
+    }
+
+    fn lookup(&self, sql: &str) -> Option<Arc<MockQuery>> {
+        lock(&self.state).queries.get(sql).cloned()
+    }
+
+    /// Resolves the command of a descriptor, or the key of a ticket, to the
+    /// query it names.
+    fn resolve(&self, cmd: &[u8]) -> std::result::Result<Resolved, Status> {
+        if let Some((command, batch)) = self.metadata(cmd) {
+            let batch = batch.map_err(|e| Status::internal(e.to_string()))?;
+            return Ok(Resolved {
+                command: command.to_string(),
+                key: Bytes::copy_from_slice(cmd),
+                query: Arc::new(MockQuery {
+                    schema: batch.schema(),
+                    partitions: vec![vec![batch]],
+                }),
+            });
+        }
+        let sql = match Any::decode(cmd) {
+            Ok(any) if any.is::<CommandStatementQuery>() => {
+                unpack::<CommandStatementQuery>(&any)
+                    .ok_or_else(|| Status::invalid_argument("Invalid command"))?
+                    .query
+            }
+            Ok(any) if any.is::<CommandPreparedStatementQuery>() => {
+                unpack::<CommandPreparedStatementQuery>(&any)
+                    .and_then(|cmd| self.prepared_sql(&cmd.prepared_statement_handle))
+                    .ok_or_else(unknown_statement)?
+            }
+            _ => String::from_utf8_lossy(cmd).into_owned(),
+        };
+        let query = self.lookup(&sql).ok_or_else(|| unknown_query(&sql))?;
+        Ok(Resolved {
+            command: sql.clone(),
+            key: Bytes::from(sql),
+            query,
+        })
+    }
+
+    /// Answers a Flight SQL metadata command from the registered tables.
+    fn metadata(
+        &self,
+        cmd: &[u8],
+    ) -> Option<(&'static str, arrow_flight::error::Result<RecordBatch>)> {
+        let any = Any::decode(cmd).ok()?;
+        let state = lock(&self.state);
+        if let Some(cmd) = unpack::<CommandGetCatalogs>(&any) {
+            let mut builder = cmd.into_builder();
+            let catalogs: BTreeSet<&str> =
+                state.tables.iter().map(|t| t.catalog.as_str()).collect();
+            for catalog in catalogs {
+                builder.append(catalog);
+            }
+            return Some(("CommandGetCatalogs", builder.build()));
+        }
+        if let Some(cmd) = unpack::<CommandGetDbSchemas>(&any) {
+            let mut builder = cmd.into_builder();
+            let schemas: BTreeSet<(&str, &str)> = state
+                .tables
+                .iter()
+                .map(|t| (t.catalog.as_str(), t.schema.as_str()))
+                .collect();
+            for (catalog, schema) in schemas {
+                builder.append(catalog, schema);
+            }
+            return Some(("CommandGetDbSchemas", builder.build()));
+        }
+        let cmd = unpack::<CommandGetTables>(&any)?;
+        let mut builder = cmd.into_builder();
+        let batch = state
+            .tables
+            .iter()
+            .try_for_each(|t| {
+                builder.append(
+                    &t.catalog,
+                    &t.schema,
+                    &t.name,
+                    &t.table_type,
+                    &t.arrow_schema,
+                )
+            })
+            .and_then(|()| builder.build());
+        Some(("CommandGetTables", batch))
+    }
+
+    fn prepared_sql(&self, handle: &Bytes) -> Option<String> {
+        lock(&self.state).prepared_statements.get(handle).cloned()
+    }
+
+    fn create_prepared_statement(
+        &self,
+        body: &[u8],
+    ) -> std::result::Result<arrow_flight::Result, Status> {
+        let request: ActionCreatePreparedStatementRequest =
+            unpack_body(body).ok_or_else(|| Status::invalid_argument("Invalid action body"))?;
+        let query = self
+            .lookup(&request.query)
+            .ok_or_else(|| unknown_query(&request.query))?;
+        let mut state = lock(&self.state);
+        let parameter_schema = state
+            .parameter_schemas
+            .get(&request.query)
+            .cloned()
+            .unwrap_or_else(|| Arc::new(Schema::empty()));
+        state.issued_statements += 1;
+        let handle = Bytes::from(format!("mock-statement-{}", state.issued_statements));
+        state
+            .prepared_statements
+            .insert(handle.clone(), request.query);
+
+        let result = ActionCreatePreparedStatementResult {
+            prepared_statement_handle: handle,
+            dataset_schema: encode_schema(&query.schema)
+                .map_err(|e| Status::internal(e.to_string()))?,
+            parameter_schema: encode_schema(&parameter_schema)
+                .map_err(|e| Status::internal(e.to_string()))?,
+        };
+        Ok(arrow_flight::Result {
+            body: result.as_any().encode_to_vec().into(),
+        })
+    }
+
+    fn close_prepared_statement(&self, body: &[u8]) -> std::result::Result<(), Status> {
+        let request: ActionClosePreparedStatementRequest =
+            unpack_body(body).ok_or_else(|| Status::invalid_argument("Invalid action body"))?;
+        lock(&self.state)
+            .prepared_statements
+            .remove(&request.prepared_statement_handle)
+            .map(|_| ())
+            .ok_or_else(unknown_statement)
+    }
+}
+
+fn unknown_query(sql: &str) -> Status {
+    Status::invalid_argument(format!("No mock result for query: {sql}"))
+}
+
+/// Returns whether `cmd` is a Flight SQL command packed in an `Any`.
+fn is_flight_sql(cmd: &[u8]) -> bool {
+    Any::decode(cmd).is_ok_and(|any| {
+        any.type_url
+            .starts_with("type.googleapis.com/arrow.flight.protocol.sql.")
+    })
+}
+
+fn unknown_statement() -> Status {
+    Status::invalid_argument("Unknown prepared statement handle")
+}
+
+fn unpack<M: ProstMessageExt>(any: &Any) -> Option<M> {
+    any.unpack().ok().flatten()
+}
+
+fn unpack_body<M: ProstMessageExt>(body: &[u8]) -> Option<M> {
+    unpack(&Any::decode(body).ok()?)
+}
+
+fn encode_schema(schema: &Schema) -> arrow::error::Result<Bytes> {
+    let IpcMessage(schema) = SchemaAsIpc::new(schema, &IpcWriteOptions::default()).try_into()?;
+    Ok(schema)
+}
+
+type ResponseStream<T> =
+    Pin<Box<dyn futures::Stream<Item = std::result::Result<T, Status>> + Send>>;
+
+#[tonic::async_trait]
+impl FlightService for MockFlightService {
+    type HandshakeStream = ResponseStream<HandshakeResponse>;
+    type ListFlightsStream = ResponseStream<FlightInfo>;
+    type DoGetStream = ResponseStream<FlightData>;
+    type DoPutStream = ResponseStream<PutResult>;
+    type DoActionStream = ResponseStream<arrow_flight::Result>;
+    type ListActionsStream = ResponseStream<ActionType>;
+    type DoExchangeStream = ResponseStream<FlightData>;
+
+    async fn handshake(
+        &self,
+        request: Request<Streaming<HandshakeRequest>>,
+    ) -> std::result::Result<Response<Self::HandshakeStream>, Status> {
+        self.enter(FlightCall::Handshake, request.metadata(), None, false)
+            .await?;
+        let password = request
+            .metadata()
+            .get("authorization")
+            .and_then(|v| v.to_str().ok())
+            .and_then(|auth| auth.strip_prefix("Basic "))
+            .and_then(|creds| BASE64_STANDARD.decode(creds).ok())
+            .and_then(|creds| String::from_utf8(creds).ok())
+            .and_then(|creds| creds.split_once(':').map(|(_, p)| p.to_string()));
+        if password.as_deref() != Some(MOCK_API_KEY) {
+            return Err(Status::unauthenticated("Invalid API key"));
+        }
+
+        let token = {
+            let mut state = lock(&self.state);
+            state.issued_tokens += 1;
+            let token = format!("mock-token-{}", state.issued_tokens);
+            state.tokens.insert(token.clone());
+            token
+        };
+        let response = HandshakeResponse {
+            protocol_version: 0,
+            payload: token.clone().into(),
+        };
+        let mut response =
+            Response::new(stream::iter([Ok(response)]).boxed() as Self::HandshakeStream);
+        let value = MetadataValue::try_from(format!("Bearer {token}"))
+            .map_err(|e| Status::internal(e.to_string()))?;
+        response.metadata_mut().insert("authorization", value);
+        Ok(response)
+    }
+
+    async fn list_flights(
+        &self,
+        _request: Request<Criteria>,
+    ) -> std::result::Result<Response<Self::ListFlightsStream>, Status> {
+        Err(Status::unimplemented("list_flights"))
+    }
+
+    async fn get_flight_info(
+        &self,
+        request: Request<FlightDescriptor>,
+    ) -> std::result::Result<Response<FlightInfo>, Status> {
+        let resolved = self.resolve(&request.get_ref().cmd);
+        let command = resolved.as_ref().map_or_else(
+            |_| String::from_utf8_lossy(&request.get_ref().cmd).into_owned(),
+            |resolved| resolved.command.clone(),
+        );
+        let flight_sql = is_flight_sql(&request.get_ref().cmd);
+        self.enter(
+            FlightCall::GetFlightInfo,
+            request.metadata(),
+            Some(command),
+            flight_sql,
+        )
+        .await?;
+        let Resolved { key, query, .. } = resolved?;
+
+        let rows: usize = query
+            .partitions
+            .iter()
+            .flatten()
+            .map(RecordBatch::num_rows)
+            .sum();
+        let mut info = FlightInfo::new()
+            .try_with_schema(&query.schema)
+            .map_err(|e| Status::internal(e.to_string()))?
+            .with_descriptor(request.into_inner())
+            .with_total_records(i64::try_from(rows).unwrap_or(-1));
+        let location = lock(&self.state).endpoint_location.clone();
+        for partition in 0..query.partitions.len() {
+            let ticket = [format!("{partition}:").as_bytes(), &key].concat();
+            let mut endpoint = FlightEndpoint::new().with_ticket(Ticket::new(ticket));
+            if let Some(location) = &location {
+                endpoint = endpoint.with_location(location);
+            }
+            info = info.with_endpoint(endpoint);
+        }
+        Ok(Response::new(info))
+    }
+
+    async fn get_schema(
+        &self,
+        request: Request<FlightDescriptor>,
+    ) -> std::result::Result<Response<SchemaResult>, Status> {
+        let resolved = self.resolve(&request.get_ref().cmd);
+        let command = resolved.as_ref().map_or_else(
+            |_| String::from_utf8_lossy(&request.get_ref().cmd).into_owned(),
+            |resolved| resolved.command.clone(),
+        );
+        let flight_sql = is_flight_sql(&request.get_ref().cmd);
+        self.enter(
+            FlightCall::GetSchema,
+            request.metadata(),
+            Some(command),
+            flight_sql,
+        )
+        .await?;
+        let query = resolved?.query;
+        let result = SchemaAsIpc::new(&query.schema, &IpcWriteOptions::default())
+            .try_into()
+            .map_err(|e: arrow::error::ArrowError| Status::internal(e.to_string()))?;
+        Ok(Response::new(result))
+    }
+
+    async fn do_get(
+        &self,
+        request: Request<Ticket>,
+    ) -> std::result::Result<Response<Self::DoGetStream>, Status> {
+        let ticket = &request.get_ref().ticket;
+        let parsed = ticket.iter().position(|&b| b == b':').and_then(|i| {
+            let partition = std::str::from_utf8(&ticket[..i])
+                .ok()?
+                .parse::<usize>()
+                .ok()?;
+            Some((partition, self.resolve(&ticket[i + 1..])))
+        });
+        let command = match &parsed {
+            Some((partition, Ok(resolved))) => format!("{partition}:{}", resolved.command),
+            _ => String::from_utf8_lossy(ticket).into_owned(),
+        };
+        self.enter(FlightCall::DoGet, request.metadata(), Some(command), false)
+            .await?;
+        let (partition, resolved) =
+            parsed.ok_or_else(|| Status::invalid_argument("Invalid ticket"))?;
+        let query = resolved?.query;
+        let batches = query
+            .partitions
+            .get(partition)
+            .cloned()
+            .ok_or_else(|| Status::invalid_argument("Invalid ticket"))?;
+
+        let stream = FlightDataEncoderBuilder::new()
+            .with_schema(query.schema.clone())
+            .build(stream::iter(batches.into_iter().map(Ok)))
+            .map_err(Status::from)
+            .boxed();
+        let mut response = Response::new(stream as Self::DoGetStream);
+        for (name, value) in lock(&self.state).response_headers.clone() {
+            if let (Ok(name), Ok(value)) = (
+                name.parse::<tonic::metadata::AsciiMetadataKey>(),
+                value.parse(),
+            ) {
+                response.metadata_mut().insert(name, value);
+            }
+        }
+        Ok(response)
+    }
+
+    async fn do_put(
+        &self,
+        request: Request<Streaming<FlightData>>,
+    ) -> std::result::Result<Response<Self::DoPutStream>, Status> {
+        let metadata = request.metadata().clone();
+        let data: Vec<FlightData> = request.into_inner().try_collect().await?;
+        // Only binding prepared statement parameters is supported.
+        let handle = data
+            .first()
+            .and_then(|data| data.flight_descriptor.as_ref())
+            .and_then(|descriptor| unpack_body::<CommandPreparedStatementQuery>(&descriptor.cmd))
+            .map(|cmd| cmd.prepared_statement_handle);
+        let sql = handle.as_ref().and_then(|handle| self.prepared_sql(handle));
+        self.enter(FlightCall::DoPut, &metadata, sql.clone(), false)
+            .await?;
+        match (handle, sql) {
+            (None, _) => return Err(Status::unimplemented("do_put only binds parameters")),
+            (Some(_), None) => return Err(unknown_statement()),
+            (Some(_), Some(_)) => {}
+        }
+        let batches =
+            flight_data_to_batches(&data).map_err(|e| Status::invalid_argument(e.to_string()))?;
+        lock(&self.state).bound_parameters.extend(batches);
+        Ok(Response::new(stream::empty().boxed()))
+    }
+
+    async fn do_action(
+        &self,
+        request: Request<Action>,
+    ) -> std::result::Result<Response<Self::DoActionStream>, Status> {
+        let action_type = request.get_ref().r#type.clone();
+        self.enter(
+            FlightCall::DoAction,
+            request.metadata(),
+            Some(action_type.clone()),
+            false,
+        )
+        .await?;
+        let body = &request.get_ref().body;
+        let results = match action_type.as_str() {
+            "CreatePreparedStatement" => vec![Ok(self.create_prepared_statement(body)?)],
+            "ClosePreparedStatement" => {
+                self.close_prepared_statement(body)?;
+                Vec::new()
+            }
+            _ => Vec::new(),
+        };
+        Ok(Response::new(stream::iter(results).boxed()))
+    }
+
+    async fn list_actions(
+        &self,
+        _request: Request<Empty>,
+    ) -> std::result::Result<Response<Self::ListActionsStream>, Status> {
+        Err(Status::unimplemented("list_actions"))
+    }
+
+    async fn do_exchange(
+        &self,
+        _request: Request<Streaming<FlightData>>,
+    ) -> std::result::Result<Response<Self::DoExchangeStream>, Status> {
+        Err(Status::unimplemented("do_exchange"))
+    }
+}
+
+async fn serve_http(
+    state: SharedState,
+    req: HttpRequestBody<Body>,
+) -> std::result::Result<HttpResponseBody<Body>, Infallible> {
+    let request = HttpRequest {
+        method: req.method().to_string(),
+        path: req.uri().path().to_string(),
+        query: req.uri().query().map(str::to_string),
+        headers: req
+            .headers()
+            .iter()
+            .filter_map(|(name, value)| Some((name.to_string(), value.to_str().ok()?.to_string())))
+            .collect(),
+    };
+    let latency = {
+        let mut state = lock(&state);
+        state.http_requests.push(request.clone());
+        state.latency
+    };
+    if !latency.is_zero() {
+        tokio::time::sleep(latency).await;
+    }
+
+    let response = {
+        let mut state = lock(&state);
+        state.http_failures.pop_front().or_else(|| {
+            if request.headers.get("x-api-key").map(String::as_str) != Some(MOCK_API_KEY) {
+                return Some(
+                    MockResponse::new(StatusCode::UNAUTHORIZED).with_body("Invalid API key"),
+                );
+            }
+            state.http_routes.get(&request.path).cloned()
+        })
+    };
+    let response = response.unwrap_or_else(|| {
+        MockResponse::new(StatusCode::NOT_FOUND)
+            .with_body(format!("No mock response for {}", request.path))
+    });
+
+    let mut builder = HttpResponseBody::builder().status(response.status.as_u16());
+    for (name, value) in &response.headers {
+        builder = builder.header(name, value);
+    }
+    Ok(builder
+        .body(Body::from(response.body))
+        .expect("invalid mock response"))
+}
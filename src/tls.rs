@@ -1,34 +1,178 @@
-use std::error::Error;
+use crate::error::{Error, Result};
+use base64::prelude::BASE64_STANDARD;
+use base64::Engine;
+use std::fmt;
 use std::str::FromStr;
+use std::time::Duration;
 use tonic::transport::channel::{ClientTlsConfig, Endpoint};
-use tonic::transport::Channel;
+use tonic::transport::{Certificate, Channel, Identity};
 
-pub fn system_tls_certificate() -> Result<tonic::transport::Certificate, Box<dyn Error>> {
+/// Connection settings applied to every Flight channel the client dials.
+#[derive(Debug, Clone, Default)]
+pub struct ChannelOptions {
+    pub connect_timeout: Option<Duration>,
+    pub timeout: Option<Duration>,
+    pub tls: TlsOptions,
+}
+
+/// The root certificates trusted by TLS connections.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum TlsRoots {
+    /// The certificates of the platform's native certificate store.
+    #[default]
+    Native,
+    /// Mozilla's root certificates, compiled into the client. Useful where
+    /// the platform store is missing or outdated, e.g. in minimal containers.
+    WebPki,
+}
+
+/// TLS settings for `https://` endpoints.
+#[derive(Debug, Clone, Default)]
+pub struct TlsOptions {
+    pub roots: TlsRoots,
+    /// PEM-encoded certificate authorities trusted in addition to `roots`.
+    pub ca_certificates: Vec<Vec<u8>>,
+    /// Client certificate presented for mutual TLS.
+    pub identity: Option<ClientIdentity>,
+    /// Name used for SNI and to verify the server certificate of Flight
+    /// endpoints, instead of the host of their URL.
+    pub domain_name: Option<String>,
+}
+
+/// A PEM-encoded client certificate chain and its PKCS #8 private key.
+#[derive(Clone)]
+pub struct ClientIdentity {
+    pub cert: Vec<u8>,
+    pub key: Vec<u8>,
+}
+
+impl fmt::Debug for ClientIdentity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ClientIdentity")
+            .field("cert", &String::from_utf8_lossy(&self.cert))
+            .field("key", &"<redacted>")
+            .finish()
+    }
+}
+
+/// Encodes DER certificates as a PEM bundle.
+fn to_pem<'a>(certs: impl IntoIterator<Item = &'a [u8]>) -> String {
+    let mut pem = String::new();
+    for cert in certs {
+        pem.push_str("-----BEGIN CERTIFICATE-----\n");
+        let encoded = BASE64_STANDARD.encode(cert);
+        for line in encoded.as_bytes().chunks(64) {
+            pem.push_str(std::str::from_utf8(line).expect("base64 is ASCII"));
+            pem.push('\n');
+        }
+        pem.push_str("-----END CERTIFICATE-----\n");
+    }
+    pem
+}
+
+pub fn system_tls_certificate() -> Result<tonic::transport::Certificate> {
     // Load root certificates found in the platform’s native certificate store.
-    let certs = rustls_native_certs::load_native_certs()?;
+    let certs = rustls_native_certs::load_native_certs()
+        .map_err(|e| Error::config_with_source("Cannot load native certificates", e))?;
+    let pem = to_pem(certs.iter().map(|cert| cert.0.as_slice()));
+    Ok(tonic::transport::Certificate::from_pem(pem))
+}
 
-    let concatenated_pems = certs
-        .iter()
-        .filter_map(|cert| {
-            let mut buf = &cert.0[..];
-            rustls_pemfile::certs(&mut buf).ok()?.pop()
-        })
-        .map(String::from_utf8)
-        .collect::<Result<String, _>>()?;
+fn webpki_tls_certificate() -> Certificate {
+    let certs = webpki_root_certs::TLS_SERVER_ROOT_CERTS;
+    Certificate::from_pem(to_pem(certs.iter().map(|cert| cert.as_ref())))
+}
+
+/// Checks that `pem` holds at least one certificate.
+fn parse_ca_certificate(pem: &[u8]) -> Result<Vec<Vec<u8>>> {
+    let certs = rustls_pemfile::certs(&mut &pem[..])
+        .map_err(|e| Error::config_with_source("Invalid CA certificate", e))?;
+    if certs.is_empty() {
+        return Err(Error::Config {
+            message: "No certificate found in CA certificate".to_string(),
+            source: None,
+        });
+    }
+    Ok(certs)
+}
 
-    Ok(tonic::transport::Certificate::from_pem(concatenated_pems))
+/// Builds the trusted roots of Flight channels as a single PEM bundle, as
+/// tonic accepts one CA certificate per channel.
+fn flight_roots(options: &TlsOptions) -> Result<Certificate> {
+    let roots = match options.roots {
+        TlsRoots::Native => system_tls_certificate()?,
+        TlsRoots::WebPki => webpki_tls_certificate(),
+    };
+    let mut pem = roots.into_inner();
+    for ca in &options.ca_certificates {
+        parse_ca_certificate(ca)?;
+        pem.extend_from_slice(ca);
+        pem.push(b'\n');
+    }
+    Ok(Certificate::from_pem(pem))
+}
+
+pub async fn new_tls_flight_channel(https_url: &str, options: &ChannelOptions) -> Result<Channel> {
+    Ok(flight_endpoint(https_url, options)?.connect().await?)
 }
 
-pub async fn new_tls_flight_channel(https_url: &str) -> Result<Channel, Box<dyn Error>> {
-    let mut endpoint = Endpoint::from_str(https_url)?;
+/// Configures the endpoint for `https_url` without connecting to it.
+pub fn flight_endpoint(https_url: &str, options: &ChannelOptions) -> Result<Endpoint> {
+    let mut endpoint = Endpoint::from_str(https_url).map_err(|e| {
+        Error::config_with_source(format!("Invalid endpoint URL \"{https_url}\""), e)
+    })?;
 
-    if https_url.starts_with("https://") {
-        let cert = system_tls_certificate()?;
-        let tls_config = ClientTlsConfig::new()
-            .ca_certificate(cert)
-            .domain_name(https_url.trim_start_matches("https://"));
+    if endpoint.uri().scheme_str() == Some("https") {
+        let tls = &options.tls;
+        let domain_name = match &tls.domain_name {
+            Some(domain_name) => domain_name.clone(),
+            None => endpoint.uri().host().unwrap_or_default().to_string(),
+        };
+        let mut tls_config = ClientTlsConfig::new()
+            .ca_certificate(flight_roots(tls)?)
+            .domain_name(domain_name);
+        if let Some(identity) = &tls.identity {
+            tls_config = tls_config.identity(Identity::from_pem(&identity.cert, &identity.key));
+        }
         endpoint = endpoint.tls_config(tls_config)?;
     }
 
-    Ok(endpoint.connect().await?)
+    if let Some(connect_timeout) = options.connect_timeout {
+        endpoint = endpoint.connect_timeout(connect_timeout);
+    }
+    if let Some(timeout) = options.timeout {
+        endpoint = endpoint.timeout(timeout);
+    }
+
+    Ok(endpoint)
+}
+
+/// Applies the TLS options to an HTTP client. The domain name override only
+/// applies to Flight channels.
+pub fn http_tls_config(
+    mut builder: reqwest::ClientBuilder,
+    options: &TlsOptions,
+) -> Result<reqwest::ClientBuilder> {
+    if options.roots == TlsRoots::WebPki {
+        builder = builder.tls_built_in_root_certs(false);
+        for cert in webpki_root_certs::TLS_SERVER_ROOT_CERTS {
+            let cert = reqwest::Certificate::from_der(cert)
+                .map_err(|e| Error::config_with_source("Invalid root certificate", e))?;
+            builder = builder.add_root_certificate(cert);
+        }
+    }
+    for ca in &options.ca_certificates {
+        for cert in parse_ca_certificate(ca)? {
+            let cert = reqwest::Certificate::from_der(&cert)
+                .map_err(|e| Error::config_with_source("Invalid CA certificate", e))?;
+            builder = builder.add_root_certificate(cert);
+        }
+    }
+    if let Some(identity) = &options.identity {
+        let identity = reqwest::Identity::from_pkcs8_pem(&identity.cert, &identity.key)
+            .map_err(|e| Error::config_with_source("Invalid client certificate", e))?;
+        builder = builder.identity(identity);
+    }
+    Ok(builder)
 }
@@ -0,0 +1,177 @@
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use arrow::array::{Int64Array, StringArray};
+    use arrow::record_batch::RecordBatch;
+    use futures::StreamExt;
+    use reqwest::StatusCode;
+    use serde_json::json;
+    use spiceai::testing::{Cassette, MockResponse, MockServer};
+    use spiceai::{Error, QueryOptions, RetryPolicy, TradingPair, QUERY_ID_HEADER};
+    use tokio_util::sync::CancellationToken;
+
+    const BLOCKS: &str = "SELECT number, hash FROM eth.blocks";
+
+    fn blocks() -> RecordBatch {
+        RecordBatch::try_from_iter([
+            ("number", Arc::new(Int64Array::from(vec![1, 2, 3])) as _),
+            (
+                "hash",
+                Arc::new(StringArray::from(vec!["0x1", "0x2", "0x3"])) as _,
+            ),
+        ])
+        .unwrap()
+    }
+
+    fn pair(pair: &str) -> TradingPair {
+        pair.parse().unwrap()
+    }
+
+    fn cassette_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("spiceai-{name}-{}.json", std::process::id()))
+    }
+
+    /// Records a query, its description and prices requests against a mock
+    /// server, which is shut down before returning.
+    async fn record(path: &Path) -> spiceai::SpiceClientBuilder {
+        let server = MockServer::start().await.unwrap();
+        server.add_query(BLOCKS, vec![blocks()]);
+        server.add_response_header(QUERY_ID_HEADER, "query-1");
+        server.mock_http("/v1/prices/pairs", MockResponse::json(&["BTC-USD"]));
+        server.mock_http(
+            "/v1/prices",
+            MockResponse::json(&json!({"BTC-USD": {"prices": {"coinbase": "42000.5"}}})),
+        );
+        let builder = server
+            .builder()
+            .retry_policy(RetryPolicy::default().with_initial_backoff(Duration::from_millis(1)));
+
+        let cassette = Cassette::record(path);
+        let client = builder
+            .clone()
+            .cassette(cassette.clone())
+            .build()
+            .await
+            .unwrap();
+        assert_eq!(
+            client
+                .query(BLOCKS)
+                .await
+                .unwrap()
+                .row_count()
+                .await
+                .unwrap(),
+            3
+        );
+        assert_eq!(client.query_info(BLOCKS).await.unwrap().endpoints, 1);
+        assert_eq!(
+            client.query_schema(BLOCKS).await.unwrap(),
+            blocks().schema()
+        );
+        server.fail_next_http(MockResponse::new(StatusCode::TOO_MANY_REQUESTS));
+        assert_eq!(
+            client.get_supported_pairs().await.unwrap(),
+            vec![pair("BTC-USD")]
+        );
+        client.get_prices(&[pair("BTC-USD")]).await.unwrap();
+        cassette.save().unwrap();
+        builder
+    }
+
+    #[tokio::test]
+    async fn test_replay_without_server() {
+        let path = cassette_path("replay");
+        let builder = record(&path).await;
+
+        let cassette = Cassette::replay(&path).unwrap();
+        assert!(cassette.is_replaying());
+        let client = builder.cassette(cassette.clone()).build().await.unwrap();
+
+        let result = client.query(BLOCKS).await.unwrap();
+        assert_eq!(result.query_id().as_deref(), Some("query-1"));
+        assert_eq!(result.concat().await.unwrap(), blocks());
+
+        let info = client.query_info(BLOCKS).await.unwrap();
+        assert_eq!(info.schema, blocks().schema());
+        assert_eq!(info.endpoints, 1);
+        assert_eq!(
+            client.query_schema(BLOCKS).await.unwrap(),
+            blocks().schema()
+        );
+
+        // The rate limited attempt is replayed and retried too.
+        assert_eq!(
+            client.get_supported_pairs().await.unwrap(),
+            vec![pair("BTC-USD")]
+        );
+        let latest = client.get_prices(&[pair("BTC-USD")]).await.unwrap();
+        assert_eq!(latest.prices[&pair("BTC-USD")].prices["coinbase"], 42000.5);
+        assert_eq!(cassette.unplayed(), 0);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_replay_unmatched_request() {
+        let path = cassette_path("unmatched");
+        let builder = record(&path).await;
+        let client = builder
+            .retry_policy(RetryPolicy::none())
+            .cassette(Cassette::replay(&path).unwrap())
+            .build()
+            .await
+            .unwrap();
+
+        assert!(matches!(
+            client.query("SELECT 1").await,
+            Err(Error::Cassette(_))
+        ));
+        assert!(matches!(
+            client.get_prices(&[pair("ETH-USD")]).await,
+            Err(Error::Cassette(_))
+        ));
+        assert!(matches!(
+            client.query_info("SELECT 1").await,
+            Err(Error::Cassette(_))
+        ));
+
+        // Each recorded response is served once.
+        client.query(BLOCKS).await.unwrap();
+        assert!(matches!(
+            client.query(BLOCKS).await,
+            Err(Error::Cassette(_))
+        ));
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_replay_with_options() {
+        let path = cassette_path("options");
+        let builder = record(&path).await;
+        let client = builder
+            .cassette(Cassette::replay(&path).unwrap())
+            .build()
+            .await
+            .unwrap();
+
+        let token = CancellationToken::new();
+        token.cancel();
+        let options = QueryOptions::new().with_cancellation_token(token);
+        assert!(matches!(
+            client.query_with_options(BLOCKS, &options).await,
+            Err(Error::Cancelled)
+        ));
+
+        let token = CancellationToken::new();
+        let options = QueryOptions::new()
+            .with_timeout(Duration::from_secs(5))
+            .with_cancellation_token(token.clone());
+        let mut result = client.query_with_options(BLOCKS, &options).await.unwrap();
+        token.cancel();
+        assert!(matches!(result.next().await, Some(Err(Error::Cancelled))));
+        assert!(result.next().await.is_none());
+        std::fs::remove_file(path).unwrap();
+    }
+}
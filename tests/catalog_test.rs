@@ -0,0 +1,207 @@
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use arrow::datatypes::{DataType, Field, Schema, SchemaRef};
+    use spiceai::testing::{FlightCall, MockServer};
+    use spiceai::{DatabaseSchema, Error, TableFilter};
+
+    fn columns(names: &[&str]) -> SchemaRef {
+        Arc::new(Schema::new(
+            names
+                .iter()
+                .map(|name| Field::new(*name, DataType::Utf8, true))
+                .collect::<Vec<_>>(),
+        ))
+    }
+
+    async fn start() -> MockServer {
+        let server = MockServer::start().await.expect("Failed to start mock");
+        server.add_table("spice", "eth", "blocks", columns(&["number", "hash"]));
+        server.add_table("spice", "eth", "block_s", columns(&["block_s"]));
+        server.add_table("spice", "eth", "blockXs", columns(&["blockXs"]));
+        server.add_table("spice", "eth", "total%", columns(&["total"]));
+        server.add_table_of_type(
+            "spice",
+            "eth",
+            "recent_blocks",
+            "VIEW",
+            columns(&["number"]),
+        );
+        server.add_table("spice", "btc", "blocks", columns(&["height"]));
+        server.add_table("spice", "btc", "total_fees", columns(&["fees"]));
+        server.add_table("other", "public", "users", columns(&["id"]));
+        server
+    }
+
+    fn schema(catalog: &str, name: &str) -> DatabaseSchema {
+        DatabaseSchema {
+            catalog: Some(catalog.to_string()),
+            name: name.to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_list_catalogs_and_schemas() {
+        let server = start().await;
+        let client = server.client().await.unwrap();
+        assert_eq!(client.list_catalogs().await.unwrap(), ["other", "spice"]);
+        assert_eq!(
+            server.flight_requests()[1].command.as_deref(),
+            Some("CommandGetCatalogs")
+        );
+
+        assert_eq!(
+            client.list_schemas(None, None).await.unwrap(),
+            [
+                schema("other", "public"),
+                schema("spice", "btc"),
+                schema("spice", "eth")
+            ]
+        );
+        assert_eq!(
+            client.list_schemas(Some("spice"), None).await.unwrap(),
+            [schema("spice", "btc"), schema("spice", "eth")]
+        );
+        assert_eq!(
+            client.list_schemas(None, Some("e_h")).await.unwrap(),
+            [schema("spice", "eth")]
+        );
+        assert!(client
+            .list_schemas(Some("missing"), None)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_list_tables_filters() {
+        let server = start().await;
+        let client = server.client().await.unwrap();
+
+        let tables = client.list_tables(&TableFilter::new()).await.unwrap();
+        assert_eq!(tables.len(), 8);
+        assert!(tables.iter().all(|t| t.arrow_schema.is_none()));
+        assert_eq!(tables[0].qualified_name(), "other.public.users");
+
+        let tables = client
+            .list_tables(
+                &TableFilter::new()
+                    .with_catalog("spice")
+                    .with_schema_pattern("eth")
+                    .with_table_pattern("%blocks")
+                    .with_arrow_schema(true),
+            )
+            .await
+            .unwrap();
+        let names: Vec<String> = tables.iter().map(|t| t.qualified_name()).collect();
+        assert_eq!(names, ["spice.eth.blocks", "spice.eth.recent_blocks"]);
+        assert_eq!(tables[0].table_type, "TABLE");
+        assert_eq!(tables[1].table_type, "VIEW");
+        assert_eq!(
+            tables[0].arrow_schema.as_ref(),
+            Some(&columns(&["number", "hash"]))
+        );
+
+        let views = client
+            .list_tables(&TableFilter::new().with_table_types(["VIEW"]))
+            .await
+            .unwrap();
+        assert_eq!(views.len(), 1);
+        assert_eq!(views[0].name, "recent_blocks");
+
+        let tables = client
+            .list_tables(&TableFilter::new().with_table_pattern("block_s"))
+            .await
+            .unwrap();
+        let names: Vec<&str> = tables.iter().map(|t| t.name.as_str()).collect();
+        assert_eq!(names, ["blockXs", "block_s"]);
+    }
+
+    #[tokio::test]
+    async fn test_table_schema() {
+        let server = start().await;
+        let client = server.client().await.unwrap();
+        assert_eq!(
+            client.table_schema("eth.blocks").await.unwrap(),
+            columns(&["number", "hash"])
+        );
+        assert_eq!(
+            client.table_schema("spice.btc.blocks").await.unwrap(),
+            columns(&["height"])
+        );
+        assert_eq!(
+            client.table_schema("recent_blocks").await.unwrap(),
+            columns(&["number"])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_table_schema_in_catalog() {
+        let server = start().await;
+        server.add_table("other", "eth", "blocks", columns(&["id"]));
+        let client = server.client().await.unwrap();
+        assert_eq!(
+            client.table_schema("spice.eth.blocks").await.unwrap(),
+            columns(&["number", "hash"])
+        );
+        assert_eq!(
+            client.table_schema("other.eth.blocks").await.unwrap(),
+            columns(&["id"])
+        );
+        assert!(matches!(
+            client.table_schema("other.btc.blocks").await,
+            Err(Error::InvalidArgument { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_table_schema_matches_exactly() {
+        let server = start().await;
+        let client = server.client().await.unwrap();
+
+        // `_` and `%` are wildcards in the patterns sent to the server, but
+        // only the exact name is accepted.
+        assert_eq!(
+            client.table_schema("eth.block_s").await.unwrap(),
+            columns(&["block_s"])
+        );
+        assert_eq!(
+            client.table_schema("total%").await.unwrap(),
+            columns(&["total"])
+        );
+        assert!(matches!(
+            client.table_schema("eth.block%").await,
+            Err(Error::InvalidArgument { .. })
+        ));
+        assert!(matches!(
+            client.table_schema("e_h.blocks").await,
+            Err(Error::InvalidArgument { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_table_schema_not_found() {
+        let server = start().await;
+        let client = server.client().await.unwrap();
+        match client.table_schema("eth.missing").await {
+            Err(Error::InvalidArgument { message }) => {
+                assert_eq!(message, "Table \"eth.missing\" not found");
+            }
+            r => panic!("Unexpected result: {r:?}"),
+        }
+        assert!(matches!(
+            client.table_schema("other.eth.blocks").await,
+            Err(Error::InvalidArgument { .. })
+        ));
+        assert!(matches!(
+            client.table_schema("a.b.c.d").await,
+            Err(Error::InvalidArgument { .. })
+        ));
+        // Invalid names are rejected before any request is sent.
+        assert!(server
+            .flight_requests()
+            .last()
+            .is_some_and(|request| request.call == FlightCall::DoGet));
+    }
+}
@@ -1,9 +1,10 @@
 #[cfg(test)]
 mod tests {
     use futures::stream::StreamExt;
-    use spiceai::Client;
+    use spiceai::{CancellationToken, Client, Error, ExportFormat, QueryOptions};
     use std::env;
     use std::path::Path;
+    use std::time::Duration;
 
     async fn new_client() -> Client {
         dotenv::from_path(Path::new(".env.local")).ok();
@@ -18,9 +19,24 @@ mod tests {
         new_client().await;
     }
 
+    #[tokio::test]
+    async fn test_builder() {
+        dotenv::from_path(Path::new(".env.local")).ok();
+        let api_key = env::var("API_KEY").expect("API_KEY not found");
+        Client::builder()
+            .api_key(api_key)
+            .flight_url("https://flight.spiceai.io")
+            .firecache_url("https://firecache.spiceai.io")
+            .http_url("https://data.spiceai.io/")
+            .connect_timeout(Duration::from_secs(10))
+            .build()
+            .await
+            .expect("Failed to create client");
+    }
+
     #[tokio::test]
     async fn test_query() {
-        let mut spice_client = new_client().await;
+        let spice_client = new_client().await;
         match spice_client.query(
             r#"SELECT number, "timestamp", base_fee_per_gas, base_fee_per_gas / 1e9 AS base_fee_per_gas_gwei FROM eth.recent_blocks limit 10"#,
             ).await {
@@ -44,9 +60,109 @@ mod tests {
             };
     }
 
+    #[test]
+    fn test_client_is_send_sync_clone() {
+        fn assert_send_sync_clone<T: Send + Sync + Clone + 'static>() {}
+        assert_send_sync_clone::<Client>();
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_queries() {
+        let spice_client = new_client().await;
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                let client = spice_client.clone();
+                tokio::spawn(async move {
+                    let mut stream = client
+                        .query("SELECT number FROM eth.recent_blocks LIMIT 10")
+                        .await
+                        .expect("query failed");
+                    let mut rows = 0;
+                    while let Some(batch) = stream.next().await {
+                        rows += batch.expect("batch failed").num_rows();
+                    }
+                    rows
+                })
+            })
+            .collect();
+        for handle in handles {
+            assert_eq!(handle.await.expect("task panicked"), 10);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_token_is_cached() {
+        let spice_client = new_client().await;
+        for _ in 0..3 {
+            let _stream = spice_client
+                .query("SELECT number FROM eth.recent_blocks LIMIT 1")
+                .await
+                .expect("query failed");
+        }
+        let metrics = spice_client.auth_metrics();
+        assert_eq!(metrics.handshakes, 1);
+        assert_eq!(metrics.token_refreshes, 0);
+    }
+
+    #[tokio::test]
+    async fn test_query_parallel_endpoints() {
+        dotenv::from_path(Path::new(".env.local")).ok();
+        let api_key = env::var("API_KEY").expect("API_KEY not found");
+        let spice_client = Client::builder()
+            .api_key(api_key)
+            .endpoint_concurrency(4)
+            .build()
+            .await
+            .expect("Failed to create client");
+        let mut stream = spice_client
+            .query("SELECT number FROM eth.blocks LIMIT 2000")
+            .await
+            .expect("query failed");
+        let mut total_rows = 0;
+        while let Some(batch) = stream.next().await {
+            total_rows += batch.expect("batch failed").num_rows();
+        }
+        assert_eq!(total_rows, 2000);
+    }
+
+    #[tokio::test]
+    async fn test_query_timeout() {
+        let spice_client = new_client().await;
+        let options = QueryOptions::new().with_timeout(Duration::from_millis(1));
+        let result = spice_client
+            .query_with_options("SELECT number FROM eth.blocks LIMIT 2000", &options)
+            .await;
+        assert!(matches!(result, Err(Error::Timeout)));
+    }
+
+    #[tokio::test]
+    async fn test_query_cancelled() {
+        let spice_client = new_client().await;
+        let token = CancellationToken::new();
+        let options = QueryOptions::new().with_cancellation_token(token.clone());
+        let mut stream = spice_client
+            .query_with_options("SELECT number FROM eth.blocks LIMIT 2000", &options)
+            .await
+            .expect("query failed");
+        token.cancel();
+        assert!(matches!(stream.next().await, Some(Err(Error::Cancelled))));
+        assert!(stream.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_query_schema() {
+        let spice_client = new_client().await;
+        let schema = spice_client
+            .query_schema(r#"SELECT number, "timestamp", base_fee_per_gas, base_fee_per_gas / 1e9 AS base_fee_per_gas_gwei FROM eth.recent_blocks limit 10"#)
+            .await
+            .expect("query_schema failed");
+        assert_eq!(schema.fields().len(), 4);
+        assert_eq!(schema.field(0).name(), "number");
+    }
+
     #[tokio::test]
     async fn test_fire_query() {
-        let mut spice_client = new_client().await;
+        let spice_client = new_client().await;
         match spice_client
             .fire_query(r#"SELECT number, "timestamp", base_fee_per_gas, base_fee_per_gas / 1e9 AS base_fee_per_gas_gwei FROM eth.recent_blocks limit 10"#)
             .await
@@ -72,7 +188,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_query_streaming() {
-        let mut spice_client = new_client().await;
+        let spice_client = new_client().await;
         match spice_client.query(
             "SELECT number, \"timestamp\", base_fee_per_gas, base_fee_per_gas / 1e9 AS base_fee_per_gas_gwei FROM eth.blocks limit 2000",
             ).await {
@@ -99,4 +215,45 @@ mod tests {
                 }
             };
     }
+
+    #[tokio::test]
+    async fn test_query_result_concat() {
+        let spice_client = new_client().await;
+        let result = spice_client
+            .query("SELECT number FROM eth.blocks LIMIT 2000")
+            .await
+            .expect("query failed");
+        let batch = result.concat().await.expect("concat failed");
+        assert_eq!(batch.num_rows(), 2000);
+        assert_eq!(batch.schema().field(0).name(), "number");
+
+        let rows = spice_client
+            .query("SELECT number FROM eth.blocks LIMIT 2000")
+            .await
+            .expect("query failed")
+            .row_count()
+            .await
+            .expect("row count failed");
+        assert_eq!(rows, 2000);
+    }
+
+    #[tokio::test]
+    async fn test_query_write_to() {
+        let spice_client = new_client().await;
+        let result = spice_client
+            .query("SELECT number FROM eth.blocks LIMIT 2000")
+            .await
+            .expect("query failed");
+        let mut buf = Vec::new();
+        let rows = result
+            .write_to(ExportFormat::ArrowStream, &mut buf)
+            .await
+            .expect("export failed");
+        assert_eq!(rows, 2000);
+
+        let reader = arrow::ipc::reader::StreamReader::try_new(buf.as_slice(), None)
+            .expect("invalid IPC stream");
+        let read: usize = reader.map(|batch| batch.unwrap().num_rows()).sum();
+        assert_eq!(read, 2000);
+    }
 }
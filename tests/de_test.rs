@@ -0,0 +1,113 @@
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use arrow::array::{
+        ArrayRef, Decimal128Array, DictionaryArray, Int64Array, StringArray,
+        TimestampMillisecondArray, UInt64Array,
+    };
+    use arrow::datatypes::Int32Type;
+    use arrow::record_batch::RecordBatch;
+    use chrono::{DateTime, TimeZone, Utc};
+    use serde::Deserialize;
+    use spiceai::{from_record_batch, Error};
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Block {
+        number: u64,
+        miner: Option<String>,
+        timestamp: DateTime<Utc>,
+        base_fee: f64,
+        chain: String,
+    }
+
+    fn blocks_batch() -> RecordBatch {
+        let columns: Vec<(&str, ArrayRef)> = vec![
+            ("number", Arc::new(UInt64Array::from(vec![u64::MAX, 2]))),
+            (
+                "miner",
+                Arc::new(StringArray::from(vec![Some("0xabc"), None])),
+            ),
+            (
+                "timestamp",
+                Arc::new(TimestampMillisecondArray::from(vec![0, 1_700_000_000_000])),
+            ),
+            (
+                "base_fee",
+                Arc::new(
+                    Decimal128Array::from(vec![12345, 100])
+                        .with_precision_and_scale(10, 2)
+                        .unwrap(),
+                ),
+            ),
+            (
+                "chain",
+                Arc::new(
+                    vec!["eth", "eth"]
+                        .into_iter()
+                        .collect::<DictionaryArray<Int32Type>>(),
+                ),
+            ),
+            ("extra", Arc::new(Int64Array::from(vec![1, 2]))),
+        ];
+        RecordBatch::try_from_iter(columns).unwrap()
+    }
+
+    #[test]
+    fn test_deserialize_struct() {
+        let blocks: Vec<Block> = from_record_batch(&blocks_batch()).unwrap();
+        assert_eq!(
+            blocks,
+            vec![
+                Block {
+                    number: u64::MAX,
+                    miner: Some("0xabc".to_string()),
+                    timestamp: Utc.timestamp_opt(0, 0).unwrap(),
+                    base_fee: 123.45,
+                    chain: "eth".to_string(),
+                },
+                Block {
+                    number: 2,
+                    miner: None,
+                    timestamp: Utc.timestamp_opt(1_700_000_000, 0).unwrap(),
+                    base_fee: 1.0,
+                    chain: "eth".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_deserialize_tuple_and_scalar() {
+        let batch = blocks_batch().project(&[0, 1]).unwrap();
+        let rows: Vec<(u64, Option<String>)> = from_record_batch(&batch).unwrap();
+        assert_eq!(rows[1], (2, None));
+
+        let batch = blocks_batch().project(&[2]).unwrap();
+        let millis: Vec<i64> = from_record_batch(&batch).unwrap();
+        assert_eq!(millis, vec![0, 1_700_000_000_000]);
+    }
+
+    #[test]
+    fn test_deserialize_errors_name_column() {
+        #[derive(Debug, Deserialize)]
+        #[allow(dead_code)]
+        struct WrongType {
+            miner: i64,
+        }
+        let err = from_record_batch::<WrongType>(&blocks_batch()).unwrap_err();
+        match err {
+            Error::Deserialize { column, .. } => assert_eq!(column.as_deref(), Some("miner")),
+            e => panic!("Unexpected error: {e:?}"),
+        }
+
+        #[derive(Debug, Deserialize)]
+        #[allow(dead_code)]
+        struct NotNullable {
+            miner: String,
+        }
+        let err = from_record_batch::<NotNullable>(&blocks_batch()).unwrap_err();
+        assert!(err.to_string().contains("\"miner\""), "{err}");
+        assert!(err.to_string().contains("null"), "{err}");
+    }
+}
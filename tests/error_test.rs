@@ -0,0 +1,26 @@
+#[cfg(test)]
+mod tests {
+    use spiceai::Error;
+    use std::error::Error as _;
+
+    fn assert_send_sync<T: Send + Sync + 'static>() {}
+
+    #[test]
+    fn test_error_is_send_sync() {
+        assert_send_sync::<Error>();
+    }
+
+    #[test]
+    fn test_error_from_status() {
+        let err: Error = tonic::Status::unavailable("try again").into();
+        match &err {
+            Error::Flight { code, .. } => assert_eq!(*code, tonic::Code::Unavailable),
+            e => panic!("Unexpected error: {e:?}"),
+        }
+        let source = err.source().expect("source should be preserved");
+        assert!(source.downcast_ref::<tonic::Status>().is_some());
+
+        let err: Error = tonic::Status::unauthenticated("bad key").into();
+        assert!(matches!(err, Error::Auth { .. }));
+    }
+}
@@ -0,0 +1,172 @@
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use std::sync::Arc;
+
+    use arrow::array::{Float64Array, Int64Array, StringArray};
+    use arrow::compute::concat_batches;
+    use arrow::datatypes::SchemaRef;
+    use arrow::ipc::reader::{FileReader, StreamReader};
+    use arrow::record_batch::RecordBatch;
+    use spiceai::testing::MockServer;
+    use spiceai::{write_batches, Error, ExportFormat};
+
+    const BLOCKS: &str = "SELECT number, hash, gas FROM eth.blocks";
+
+    fn blocks(numbers: std::ops::Range<i64>) -> RecordBatch {
+        let hashes: Vec<String> = numbers.clone().map(|n| format!("0x{n:x}")).collect();
+        let gas: Vec<Option<f64>> = numbers
+            .clone()
+            .map(|n| (n % 3 != 0).then_some(n as f64 * 1.5))
+            .collect();
+        RecordBatch::try_from_iter([
+            (
+                "number",
+                Arc::new(Int64Array::from_iter_values(numbers)) as _,
+            ),
+            ("hash", Arc::new(StringArray::from(hashes)) as _),
+            ("gas", Arc::new(Float64Array::from(gas)) as _),
+        ])
+        .unwrap()
+    }
+
+    fn formats() -> Vec<ExportFormat> {
+        vec![
+            ExportFormat::Csv,
+            ExportFormat::NdJson,
+            ExportFormat::ArrowFile,
+            ExportFormat::ArrowStream,
+            #[cfg(feature = "parquet")]
+            "parquet".parse().unwrap(),
+        ]
+    }
+
+    /// Reads back what was written in `format`, as a single batch.
+    fn read_back(format: ExportFormat, data: Vec<u8>, schema: SchemaRef) -> RecordBatch {
+        let batches: Vec<RecordBatch> = match format {
+            ExportFormat::Csv => arrow::csv::ReaderBuilder::new(schema.clone())
+                .with_header(true)
+                .build(Cursor::new(data))
+                .unwrap()
+                .collect::<Result<_, _>>()
+                .unwrap(),
+            ExportFormat::NdJson => arrow::json::ReaderBuilder::new(schema.clone())
+                .build(Cursor::new(data))
+                .unwrap()
+                .collect::<Result<_, _>>()
+                .unwrap(),
+            ExportFormat::ArrowFile => FileReader::try_new(Cursor::new(data), None)
+                .unwrap()
+                .collect::<Result<_, _>>()
+                .unwrap(),
+            ExportFormat::ArrowStream => StreamReader::try_new(Cursor::new(data), None)
+                .unwrap()
+                .collect::<Result<_, _>>()
+                .unwrap(),
+            #[cfg(feature = "parquet")]
+            ExportFormat::Parquet { .. } => {
+                parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder::try_new(
+                    bytes::Bytes::from(data),
+                )
+                .unwrap()
+                .build()
+                .unwrap()
+                .collect::<Result<_, _>>()
+                .unwrap()
+            }
+            format => panic!("Unexpected format {format}"),
+        };
+        concat_batches(&schema, &batches).unwrap()
+    }
+
+    #[test]
+    fn test_export_format_from_str() {
+        assert_eq!("csv".parse::<ExportFormat>().unwrap(), ExportFormat::Csv);
+        assert_eq!(
+            "JSONL".parse::<ExportFormat>().unwrap(),
+            ExportFormat::NdJson
+        );
+        assert_eq!(
+            "arrow".parse::<ExportFormat>().unwrap(),
+            ExportFormat::ArrowFile
+        );
+        assert_eq!(
+            "arrows".parse::<ExportFormat>().unwrap(),
+            ExportFormat::ArrowStream
+        );
+        assert!(matches!(
+            "xlsx".parse::<ExportFormat>(),
+            Err(Error::InvalidArgument { .. })
+        ));
+    }
+
+    #[cfg(feature = "parquet")]
+    #[test]
+    fn test_parquet_format_from_str() {
+        assert!(matches!(
+            "parquet".parse::<ExportFormat>().unwrap(),
+            ExportFormat::Parquet { .. }
+        ));
+    }
+
+    #[test]
+    fn test_write_batches_round_trip() {
+        let batches = [blocks(0..10), blocks(10..25)];
+        let expected = concat_batches(&batches[0].schema(), &batches).unwrap();
+        for format in formats() {
+            let mut data = Vec::new();
+            assert_eq!(write_batches(&batches, format, &mut data).unwrap(), 25);
+            assert_eq!(
+                read_back(format, data, expected.schema()),
+                expected,
+                "{format}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_write_to_round_trip() {
+        let server = MockServer::start().await.expect("Failed to start mock");
+        server.add_partitioned_query(
+            BLOCKS,
+            vec![vec![blocks(0..10), blocks(10..20)], vec![blocks(20..30)]],
+        );
+        let client = server.client().await.unwrap();
+        let expected = blocks(0..30);
+        for format in formats() {
+            let mut data = Vec::new();
+            let result = client.query(BLOCKS).await.unwrap();
+            assert_eq!(result.write_to(format, &mut data).await.unwrap(), 30);
+            assert_eq!(
+                read_back(format, data, expected.schema()),
+                expected,
+                "{format}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_query_to_file_round_trip() {
+        let server = MockServer::start().await.expect("Failed to start mock");
+        server.add_partitioned_query(
+            BLOCKS,
+            vec![vec![blocks(0..10), blocks(10..20)], vec![blocks(20..30)]],
+        );
+        let client = server.client().await.unwrap();
+        let expected = blocks(0..30);
+        let path = std::env::temp_dir().join(format!("spiceai-export-{}.out", std::process::id()));
+        for format in formats() {
+            assert_eq!(
+                client.query_to_file(BLOCKS, &path, format).await.unwrap(),
+                30
+            );
+            let data = std::fs::read(&path).unwrap();
+            assert_eq!(
+                read_back(format, data, expected.schema()),
+                expected,
+                "{format}"
+            );
+        }
+        std::fs::remove_file(path).unwrap();
+    }
+}
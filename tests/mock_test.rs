@@ -0,0 +1,1011 @@
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use arrow::array::{Int64Array, StringArray};
+    use arrow::record_batch::RecordBatch;
+    use chrono::{Duration as ChronoDuration, Utc};
+    use futures::stream::BoxStream;
+    use futures::TryStreamExt;
+    use reqwest::StatusCode;
+    use serde::Deserialize;
+    use serde_json::json;
+    use spiceai::testing::{FlightCall, MockResponse, MockServer, MOCK_API_KEY};
+    use spiceai::{
+        historical_prices_from_record_batch, historical_prices_to_record_batch, prices_schema,
+        write_batches, CancellationToken, Error, ExportFormat, Granularity, LatestPricesResponse,
+        PriceTick, QueryOptions, QueryProtocol, RetryPolicy, SpiceClientBuilder, TradingPair,
+    };
+
+    const BLOCKS: &str = "SELECT number, hash FROM eth.blocks";
+
+    fn blocks(numbers: std::ops::Range<i64>) -> RecordBatch {
+        let hashes: Vec<String> = numbers.clone().map(|n| format!("0x{n:x}")).collect();
+        RecordBatch::try_from_iter([
+            (
+                "number",
+                Arc::new(Int64Array::from_iter_values(numbers)) as _,
+            ),
+            ("hash", Arc::new(StringArray::from(hashes)) as _),
+        ])
+        .unwrap()
+    }
+
+    fn pair(pair: &str) -> TradingPair {
+        pair.parse().unwrap()
+    }
+
+    async fn start() -> MockServer {
+        let server = MockServer::start().await.expect("Failed to start mock");
+        server.add_query(BLOCKS, vec![blocks(0..10), blocks(10..20)]);
+        server
+    }
+
+    fn cancel_requests(server: &MockServer) -> usize {
+        server
+            .flight_requests()
+            .iter()
+            .filter(|request| {
+                request.call == FlightCall::DoAction
+                    && request.command.as_deref() == Some("CancelFlightInfo")
+            })
+            .count()
+    }
+
+    /// Waits for the `CancelFlightInfo` action sent in the background.
+    async fn wait_for_cancel(server: &MockServer) {
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while cancel_requests(server) == 0 {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("CancelFlightInfo was not sent");
+    }
+
+    #[tokio::test]
+    async fn test_query_authenticates() {
+        let server = start().await;
+        let client = server.client().await.unwrap();
+        let result = client.query(BLOCKS).await.unwrap();
+        assert_eq!(result.total_records(), Some(20));
+        assert_eq!(result.schema().unwrap().field(0).name(), "number");
+        assert_eq!(result.row_count().await.unwrap(), 20);
+
+        let requests = server.flight_requests();
+        assert_eq!(requests[0].call, FlightCall::Handshake);
+        for request in &requests[1..] {
+            let auth = request.authorization.as_deref().unwrap();
+            assert!(auth.starts_with("Bearer mock-token-"), "{auth}");
+        }
+        assert_eq!(client.auth_metrics().handshakes, 1);
+    }
+
+    #[tokio::test]
+    async fn test_invalid_api_key() {
+        let server = start().await;
+        let client = server
+            .builder()
+            .api_key("mock-app|wrong")
+            .build()
+            .await
+            .unwrap();
+        assert!(matches!(
+            client.query(BLOCKS).await,
+            Err(Error::Auth { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_malformed_api_key() {
+        let server = start().await;
+        for api_key in ["ab", "|secret", "mock-app|", "|"] {
+            let client = server.builder().api_key(api_key).build().await.unwrap();
+            assert!(
+                matches!(client.query(BLOCKS).await, Err(Error::InvalidApiKey)),
+                "{api_key}"
+            );
+        }
+        // Malformed keys are rejected before the handshake is attempted.
+        assert!(server.flight_requests().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_token_refresh() {
+        let server = start().await;
+        let client = server.client().await.unwrap();
+        client.query(BLOCKS).await.unwrap();
+        server.expire_tokens();
+        client.query(BLOCKS).await.unwrap();
+        let metrics = client.auth_metrics();
+        assert_eq!(metrics.handshakes, 2);
+        assert_eq!(metrics.token_refreshes, 1);
+    }
+
+    #[tokio::test]
+    async fn test_retry_transient_errors() {
+        let server = start().await;
+        let client = server
+            .builder()
+            .retry_policy(RetryPolicy::default().with_initial_backoff(Duration::from_millis(1)))
+            .build()
+            .await
+            .unwrap();
+        server.fail_next(
+            FlightCall::GetFlightInfo,
+            tonic::Status::unavailable("busy"),
+        );
+        server.fail_next(FlightCall::DoGet, tonic::Status::unavailable("busy"));
+        assert_eq!(
+            client
+                .query(BLOCKS)
+                .await
+                .unwrap()
+                .row_count()
+                .await
+                .unwrap(),
+            20
+        );
+
+        server.fail_next(FlightCall::GetFlightInfo, tonic::Status::internal("boom"));
+        match client.query(BLOCKS).await {
+            Err(Error::Flight { code, .. }) => assert_eq!(code, tonic::Code::Internal),
+            Err(e) => panic!("Unexpected error: {e:?}"),
+            Ok(_) => panic!("Expected an error"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_parallel_endpoints() {
+        let server = start().await;
+        let partitions = (0..8)
+            .map(|i| vec![blocks(i * 100..(i + 1) * 100)])
+            .collect();
+        server.add_partitioned_query("SELECT * FROM eth.big", partitions);
+        let client = server
+            .builder()
+            .endpoint_concurrency(4)
+            .build()
+            .await
+            .unwrap();
+        let info = client.query_info("SELECT * FROM eth.big").await.unwrap();
+        assert_eq!(info.endpoints, 8);
+
+        let batch = client
+            .query("SELECT * FROM eth.big")
+            .await
+            .unwrap()
+            .concat()
+            .await
+            .unwrap();
+        assert_eq!(batch.num_rows(), 800);
+    }
+
+    #[tokio::test]
+    async fn test_endpoint_locations() {
+        let server = start().await;
+        let port = server.flight_url().rsplit(':').next().unwrap().to_string();
+        let client = server
+            .builder()
+            .retry_policy(RetryPolicy::none())
+            .build()
+            .await
+            .unwrap();
+        let rows = |location: String| {
+            server.set_endpoint_location(location);
+            async { client.query(BLOCKS).await?.row_count().await }
+        };
+
+        for scheme in ["grpc", "grpc+tcp", "http"] {
+            assert_eq!(
+                rows(format!("{scheme}://localhost:{port}")).await.unwrap(),
+                20
+            );
+        }
+        // The mock only speaks plaintext, so dialing it over TLS fails.
+        assert!(rows(format!("grpc+tls://localhost:{port}")).await.is_err());
+        match rows("s3://bucket/results".to_string()).await {
+            Err(e @ Error::Decode(_)) => {
+                assert!(e.to_string().contains("s3://bucket/results"), "{e}");
+            }
+            r => panic!("Unexpected result: {r:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_flight_sql_protocol() {
+        let server = start().await;
+        let client = server
+            .builder()
+            .query_protocol(QueryProtocol::FlightSql)
+            .build()
+            .await
+            .unwrap();
+        assert_eq!(
+            client
+                .query(BLOCKS)
+                .await
+                .unwrap()
+                .row_count()
+                .await
+                .unwrap(),
+            20
+        );
+        assert_eq!(
+            client
+                .fire_query(BLOCKS)
+                .await
+                .unwrap()
+                .row_count()
+                .await
+                .unwrap(),
+            20
+        );
+    }
+
+    #[tokio::test]
+    async fn test_local_uses_flight_sql() {
+        let server = start().await;
+        let sends_flight_sql = |builder: SpiceClientBuilder| async {
+            let client = builder
+                .api_key(MOCK_API_KEY)
+                .flight_url(server.flight_url())
+                .firecache_url(server.flight_url())
+                .http_url(server.http_url())
+                .build()
+                .await
+                .unwrap();
+            client.query(BLOCKS).await.unwrap();
+            let requests = server.flight_requests();
+            let request = requests
+                .iter()
+                .rfind(|request| request.call == FlightCall::GetFlightInfo)
+                .unwrap();
+            request.flight_sql
+        };
+
+        // The local runtime only speaks Flight SQL, unlike the cloud endpoints.
+        assert!(sends_flight_sql(SpiceClientBuilder::local()).await);
+        assert!(!sends_flight_sql(SpiceClientBuilder::new()).await);
+    }
+
+    #[tokio::test]
+    async fn test_query_headers_and_rows() {
+        #[derive(Deserialize)]
+        struct Block {
+            number: i64,
+            hash: String,
+        }
+
+        let server = start().await;
+        server.add_response_header(spiceai::QUERY_ID_HEADER, "query-1");
+        let client = server.client().await.unwrap();
+        let result = client.query(BLOCKS).await.unwrap();
+        assert_eq!(result.query_id().as_deref(), Some("query-1"));
+
+        let blocks: Vec<Block> = client.query_as_vec(BLOCKS).await.unwrap();
+        assert_eq!(blocks.len(), 20);
+        assert_eq!(blocks[11].number, 11);
+        assert_eq!(blocks[11].hash, "0xb");
+    }
+
+    #[tokio::test]
+    async fn test_query_timeout() {
+        let server = start().await;
+        let client = server.client().await.unwrap();
+        server.set_latency(Duration::from_millis(200));
+        let options = QueryOptions::new().with_timeout(Duration::from_millis(50));
+        assert!(matches!(
+            client.query_with_options(BLOCKS, &options).await,
+            Err(Error::Timeout)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_query_timeout_after_flight_info() {
+        let server = start().await;
+        let client = server.client().await.unwrap();
+        client.query(BLOCKS).await.unwrap();
+
+        // `get_flight_info` completes, then the deadline passes in `do_get`.
+        server.set_latency(Duration::from_millis(300));
+        let options = QueryOptions::new().with_timeout(Duration::from_millis(450));
+        assert!(matches!(
+            client.query_with_options(BLOCKS, &options).await,
+            Err(Error::Timeout)
+        ));
+        wait_for_cancel(&server).await;
+    }
+
+    #[tokio::test]
+    async fn test_query_timeout_while_streaming() {
+        let server = MockServer::start().await.unwrap();
+        server.add_partitioned_query(BLOCKS, vec![vec![blocks(0..10)], vec![blocks(10..20)]]);
+        let client = server.client().await.unwrap();
+        let options = QueryOptions::new().with_timeout(Duration::from_millis(300));
+        let mut result = client.query_with_options(BLOCKS, &options).await.unwrap();
+        assert_eq!(result.try_next().await.unwrap().unwrap(), blocks(0..10));
+
+        // The second endpoint is fetched after the deadline.
+        server.set_latency(Duration::from_secs(1));
+        assert!(matches!(result.try_next().await, Err(Error::Timeout)));
+        assert!(result.try_next().await.unwrap().is_none());
+        wait_for_cancel(&server).await;
+    }
+
+    #[tokio::test]
+    async fn test_query_cancelled() {
+        let server = start().await;
+        let client = server.client().await.unwrap();
+        server.set_latency(Duration::from_millis(300));
+        let token = CancellationToken::new();
+        let options = QueryOptions::new().with_cancellation_token(token.clone());
+        let cancel = tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            token.cancel();
+        });
+        assert!(matches!(
+            client.query_with_options(BLOCKS, &options).await,
+            Err(Error::Cancelled)
+        ));
+        cancel.await.unwrap();
+        // There is no `FlightInfo` to cancel before `get_flight_info` returns.
+        assert_eq!(cancel_requests(&server), 0);
+    }
+
+    #[tokio::test]
+    async fn test_query_cancelled_while_streaming() {
+        let server = start().await;
+        let client = server.client().await.unwrap();
+        let token = CancellationToken::new();
+        let options = QueryOptions::new().with_cancellation_token(token.clone());
+        let mut result = client.query_with_options(BLOCKS, &options).await.unwrap();
+        assert_eq!(result.try_next().await.unwrap().unwrap(), blocks(0..10));
+
+        token.cancel();
+        assert!(matches!(result.try_next().await, Err(Error::Cancelled)));
+        assert!(result.try_next().await.unwrap().is_none());
+        wait_for_cancel(&server).await;
+        assert_eq!(cancel_requests(&server), 1);
+    }
+
+    #[tokio::test]
+    async fn test_unknown_query() {
+        let server = start().await;
+        let client = server.client().await.unwrap();
+        match client.query("SELECT 1").await {
+            Err(Error::Flight { code, .. }) => assert_eq!(code, tonic::Code::InvalidArgument),
+            Err(e) => panic!("Unexpected error: {e:?}"),
+            Ok(_) => panic!("Expected an error"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_prices() {
+        let server = start().await;
+        server.mock_http(
+            "/v1/prices/pairs",
+            MockResponse::json(&["BTC-USD", "ETH-USD"]),
+        );
+        server.mock_http(
+            "/v1/prices",
+            MockResponse::json(&json!({
+                "BTC-USD": {"prices": {"coinbase": "42000.5"}, "minPrice": "42000.5"}
+            })),
+        );
+        server.mock_http(
+            "/v1/prices/historical",
+            MockResponse::json(&json!({
+                "BTC-USD": [{"timestamp": "2024-01-01T00:00:00Z", "price": 41000.0}]
+            })),
+        );
+        let client = server.client().await.unwrap();
+
+        let pairs = client.get_supported_pairs().await.unwrap();
+        assert_eq!(pairs, vec![pair("BTC-USD"), pair("ETH-USD")]);
+
+        let latest = client.get_prices(&[pair("BTC-USD")]).await.unwrap();
+        assert_eq!(latest.prices[&pair("BTC-USD")].prices["coinbase"], 42000.5);
+
+        let historical = client
+            .get_historical_prices(&[pair("BTC-USD")], None, None, None)
+            .await
+            .unwrap();
+        assert_eq!(historical[&pair("BTC-USD")][0].price, 41000.0);
+
+        let requests = server.http_requests();
+        assert_eq!(requests.len(), 3);
+        for request in &requests {
+            assert_eq!(request.headers["x-api-key"], MOCK_API_KEY);
+        }
+        assert_eq!(requests[1].query.as_deref(), Some("pairs=BTC-USD"));
+    }
+
+    #[tokio::test]
+    async fn test_prices_with_options() {
+        let server = start().await;
+        server.mock_http("/v1/prices/pairs", MockResponse::json(&["BTC-USD"]));
+        server.mock_http(
+            "/v1/prices",
+            MockResponse::json(&json!({"BTC-USD": {"prices": {"coinbase": "42000.5"}}})),
+        );
+        server.mock_http("/v1/prices/historical", MockResponse::json(&json!({})));
+        let client = server.client().await.unwrap();
+        server.set_latency(Duration::from_millis(300));
+
+        let timeout = QueryOptions::new().with_timeout(Duration::from_millis(50));
+        assert!(matches!(
+            client.get_supported_pairs_with_options(&timeout).await,
+            Err(Error::Timeout)
+        ));
+        assert!(matches!(
+            client
+                .get_prices_with_options(&[pair("BTC-USD")], &timeout)
+                .await,
+            Err(Error::Timeout)
+        ));
+        let end = Utc::now();
+        let start = end - ChronoDuration::hours(1);
+        assert!(matches!(
+            client
+                .get_historical_prices_with_options(
+                    &[pair("BTC-USD")],
+                    Some(start),
+                    Some(end),
+                    None,
+                    &timeout
+                )
+                .await,
+            Err(Error::Timeout)
+        ));
+        let mut prices = client
+            .stream_historical_prices_with_options(
+                &[pair("BTC-USD")],
+                start,
+                end,
+                Granularity::Minute,
+                &timeout,
+            )
+            .unwrap();
+        assert!(matches!(prices.try_next().await, Err(Error::Timeout)));
+        assert!(prices.try_next().await.unwrap().is_none());
+
+        let token = CancellationToken::new();
+        let cancellable = QueryOptions::new().with_cancellation_token(token.clone());
+        let cancel = tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            token.cancel();
+        });
+        assert!(matches!(
+            client
+                .get_prices_with_options(&[pair("BTC-USD")], &cancellable)
+                .await,
+            Err(Error::Cancelled)
+        ));
+        cancel.await.unwrap();
+        let mut ticks = client
+            .subscribe_prices_with_options(&[pair("BTC-USD")], Duration::from_secs(1), &cancellable)
+            .unwrap();
+        assert!(matches!(ticks.try_next().await, Err(Error::Cancelled)));
+        assert!(ticks.try_next().await.unwrap().is_none());
+
+        // Subscriptions time out each poll and keep polling.
+        let mut ticks = client
+            .subscribe_prices_with_options(&[pair("BTC-USD")], Duration::from_millis(10), &timeout)
+            .unwrap();
+        assert!(matches!(ticks.try_next().await, Err(Error::Timeout)));
+        server.set_latency(Duration::ZERO);
+        let tick = tokio::time::timeout(Duration::from_secs(5), ticks.try_next())
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        assert_eq!(tick.pair, pair("BTC-USD"));
+    }
+
+    #[tokio::test]
+    async fn test_prices_errors() {
+        let server = start().await;
+        server.mock_http("/v1/prices/pairs", MockResponse::json(&["BTC-USD"]));
+        let client = server
+            .builder()
+            .retry_policy(RetryPolicy::none())
+            .build()
+            .await
+            .unwrap();
+
+        server.fail_next_http(
+            MockResponse::new(StatusCode::TOO_MANY_REQUESTS).with_header("retry-after", "7"),
+        );
+        match client.get_supported_pairs().await {
+            Err(Error::RateLimited { retry_after }) => {
+                assert_eq!(retry_after, Some(Duration::from_secs(7)));
+            }
+            r => panic!("Unexpected result: {r:?}"),
+        }
+
+        server.fail_next_http(MockResponse::new(StatusCode::BAD_REQUEST).with_body("bad pair"));
+        match client.get_supported_pairs().await {
+            Err(Error::BadRequest { message }) => assert_eq!(message, "bad pair"),
+            r => panic!("Unexpected result: {r:?}"),
+        }
+
+        assert!(client.get_supported_pairs().await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_retry_after() {
+        let server = start().await;
+        server.mock_http("/v1/prices/pairs", MockResponse::json(&["BTC-USD"]));
+        let client = server
+            .builder()
+            .retry_policy(RetryPolicy::none())
+            .build()
+            .await
+            .unwrap();
+
+        let date = (Utc::now() + ChronoDuration::seconds(30)).format("%a, %d %b %Y %H:%M:%S GMT");
+        server.fail_next_http(
+            MockResponse::new(StatusCode::TOO_MANY_REQUESTS)
+                .with_header("retry-after", date.to_string()),
+        );
+        match client.get_supported_pairs().await {
+            Err(Error::RateLimited {
+                retry_after: Some(retry_after),
+            }) => {
+                assert!(retry_after > Duration::from_secs(25), "{retry_after:?}");
+                assert!(retry_after <= Duration::from_secs(30), "{retry_after:?}");
+            }
+            r => panic!("Unexpected result: {r:?}"),
+        }
+
+        server.fail_next_http(
+            MockResponse::new(StatusCode::TOO_MANY_REQUESTS)
+                .with_header("retry-after", "Sun, 06 Nov 1994 08:49:37 GMT"),
+        );
+        assert!(matches!(
+            client.get_supported_pairs().await,
+            Err(Error::RateLimited {
+                retry_after: Some(Duration::ZERO)
+            })
+        ));
+
+        // An advertised delay longer than the maximum backoff is capped.
+        let delays = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let recorded = delays.clone();
+        let client = server
+            .builder()
+            .retry_policy(
+                RetryPolicy::default()
+                    .with_max_backoff(Duration::from_millis(10))
+                    .on_retry(move |event| recorded.lock().unwrap().push(event.delay)),
+            )
+            .build()
+            .await
+            .unwrap();
+        server.fail_next_http(
+            MockResponse::new(StatusCode::TOO_MANY_REQUESTS).with_header("retry-after", "3600"),
+        );
+        tokio::time::timeout(Duration::from_secs(5), client.get_supported_pairs())
+            .await
+            .expect("Retry-After was not capped")
+            .unwrap();
+        assert_eq!(*delays.lock().unwrap(), [Duration::from_millis(10)]);
+    }
+
+    #[tokio::test]
+    async fn test_prices_check_api_key() {
+        let server = start().await;
+        server.mock_http("/v1/prices/pairs", MockResponse::json(&["BTC-USD"]));
+
+        let client = server
+            .builder()
+            .api_key("mock-app|wrong-secret")
+            .retry_policy(RetryPolicy::none())
+            .build()
+            .await
+            .unwrap();
+        match client.get_supported_pairs().await {
+            Err(Error::Http { status, message }) => {
+                assert_eq!(status, StatusCode::UNAUTHORIZED);
+                assert_eq!(message, "Invalid API key");
+            }
+            r => panic!("Unexpected result: {r:?}"),
+        }
+
+        let client = SpiceClientBuilder::new()
+            .flight_url(server.flight_url())
+            .firecache_url(server.flight_url())
+            .http_url(server.http_url())
+            .retry_policy(RetryPolicy::none())
+            .build()
+            .await
+            .unwrap();
+        match client.get_supported_pairs().await {
+            Err(Error::Http { status, .. }) => assert_eq!(status, StatusCode::UNAUTHORIZED),
+            r => panic!("Unexpected result: {r:?}"),
+        }
+        let requests = server.http_requests();
+        assert_eq!(requests[0].headers["x-api-key"], "mock-app|wrong-secret");
+        assert!(!requests[1].headers.contains_key("x-api-key"));
+    }
+
+    #[tokio::test]
+    async fn test_historical_prices_validation() {
+        let server = start().await;
+        server.mock_http("/v1/prices/historical", MockResponse::json(&json!({})));
+        let client = server.client().await.unwrap();
+        let now = Utc::now();
+        let day_ago = now - ChronoDuration::days(1);
+
+        let invalid = [
+            (vec![], None, None, None),
+            (vec![pair("BTC-USD")], Some(now), Some(day_ago), None),
+            (
+                vec![pair("BTC-USD")],
+                Some(now + ChronoDuration::hours(1)),
+                None,
+                None,
+            ),
+        ];
+        for (pairs, start, end, granularity) in invalid {
+            match client
+                .get_historical_prices(&pairs, start, end, granularity)
+                .await
+            {
+                Err(Error::InvalidArgument { .. }) => {}
+                r => panic!("Unexpected result: {r:?}"),
+            }
+        }
+        assert!(server.http_requests().is_empty());
+
+        // Ranges are only limited when a maximum is configured.
+        let two_days_ago = now - ChronoDuration::days(2);
+        for start in [day_ago, two_days_ago] {
+            client
+                .get_historical_prices(
+                    &[pair("BTC-USD")],
+                    Some(start),
+                    Some(now),
+                    Some(Granularity::Minute),
+                )
+                .await
+                .unwrap();
+        }
+        let requests = server.http_requests();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(
+            requests[0].query.as_deref(),
+            Some(
+                format!(
+                    "pairs=BTC-USD&start={}&end={}&granularity=1m",
+                    day_ago.timestamp(),
+                    now.timestamp()
+                )
+                .as_str()
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn test_max_historical_intervals() {
+        let server = start().await;
+        server.mock_http("/v1/prices/historical", MockResponse::json(&json!({})));
+        let client = server
+            .builder()
+            .max_historical_intervals(60)
+            .build()
+            .await
+            .unwrap();
+        let end = Utc::now();
+        let start = end - ChronoDuration::hours(3);
+
+        match client
+            .get_historical_prices(
+                &[pair("BTC-USD")],
+                Some(start),
+                Some(end),
+                Some(Granularity::Minute),
+            )
+            .await
+        {
+            Err(Error::InvalidArgument { message }) => {
+                assert!(message.contains("maximum of 1 hour"), "{message}");
+            }
+            r => panic!("Unexpected result: {r:?}"),
+        }
+        assert!(server.http_requests().is_empty());
+
+        let prices: Vec<_> = client
+            .stream_historical_prices(&[pair("BTC-USD")], start, end, Granularity::Minute)
+            .unwrap()
+            .try_collect()
+            .await
+            .unwrap();
+        assert!(prices.is_empty());
+        assert_eq!(server.http_requests().len(), 3);
+    }
+
+    #[test]
+    fn test_granularity_from_str() {
+        for granularity in Granularity::ALL {
+            assert_eq!(
+                granularity.to_string().parse::<Granularity>().unwrap(),
+                granularity
+            );
+        }
+        let err = "2h".parse::<Granularity>().unwrap_err().to_string();
+        assert!(err.contains("1m, 5m, 15m, 1h, 4h, 1d"), "{err}");
+    }
+
+    #[tokio::test]
+    async fn test_stream_historical_prices() {
+        let server = start().await;
+        let end = Utc::now();
+        let start = end - ChronoDuration::days(3);
+        // Every window returns the same prices, one per hour of the range and
+        // one before it, so that windows overlap.
+        let hourly = |pair_offset: f64| {
+            (-1..72)
+                .rev()
+                .map(|hour| {
+                    let timestamp = start + ChronoDuration::hours(hour);
+                    json!({"timestamp": timestamp, "price": pair_offset + hour as f64})
+                })
+                .collect::<Vec<_>>()
+        };
+        server.mock_http(
+            "/v1/prices/historical",
+            MockResponse::json(&json!({"BTC-USD": hourly(0.0), "ETH-USD": hourly(1000.0)})),
+        );
+        let client = server
+            .builder()
+            .prices_concurrency(2)
+            .build()
+            .await
+            .unwrap();
+
+        let prices: Vec<_> = client
+            .stream_historical_prices(
+                &[pair("BTC-USD"), pair("ETH-USD")],
+                start,
+                end,
+                Granularity::Minute,
+            )
+            .unwrap()
+            .try_collect()
+            .await
+            .unwrap();
+        assert_eq!(prices.len(), 2 * 72);
+        for (i, (pair, price)) in prices.iter().enumerate() {
+            let expected = if i % 2 == 0 { "BTC-USD" } else { "ETH-USD" };
+            assert_eq!(pair.to_string(), expected);
+            assert_eq!(price.timestamp, start + ChronoDuration::hours(i as i64 / 2));
+        }
+
+        let mut windows: Vec<_> = server
+            .http_requests()
+            .into_iter()
+            .map(|request| request.query.unwrap())
+            .collect();
+        windows.sort();
+        let expected: Vec<_> = (0..3)
+            .map(|day| {
+                format!(
+                    "pairs=BTC-USD%2CETH-USD&start={}&end={}&granularity=1m",
+                    (start + ChronoDuration::days(day)).timestamp(),
+                    (start + ChronoDuration::days(day + 1)).timestamp()
+                )
+            })
+            .collect();
+        assert_eq!(windows, expected);
+    }
+
+    #[test]
+    fn test_trading_pair() {
+        let btc_usd = pair(" btc-Usd ");
+        assert_eq!((btc_usd.base(), btc_usd.quote()), ("BTC", "USD"));
+        assert_eq!(btc_usd, TradingPair::new("BTC", "USD").unwrap());
+        assert_eq!(btc_usd.to_string(), "BTC-USD");
+        assert_eq!(serde_json::to_value(&btc_usd).unwrap(), json!("BTC-USD"));
+        assert_eq!(
+            serde_json::from_value::<TradingPair>(json!("eth-usdc")).unwrap(),
+            pair("ETH-USDC")
+        );
+
+        for invalid in ["BTC", "BTC-", "-USD", "BTC-USD-EUR", "BTC/USD", "BTC-US D"] {
+            assert!(
+                matches!(
+                    invalid.parse::<TradingPair>(),
+                    Err(Error::InvalidArgument { .. })
+                ),
+                "{invalid}"
+            );
+        }
+        assert!(serde_json::from_value::<TradingPair>(json!("BTCUSD")).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_pair_validation() {
+        let server = start().await;
+        server.mock_http(
+            "/v1/prices/pairs",
+            MockResponse::json(&["BTC-USD", "ETH-USD"]),
+        );
+        server.mock_http(
+            "/v1/prices",
+            MockResponse::json(&json!({"BTC-USD": {"prices": {"coinbase": "42000.5"}}})),
+        );
+        let client = server
+            .builder()
+            .validate_pairs(Duration::from_secs(60))
+            .build()
+            .await
+            .unwrap();
+
+        client.get_prices(&[pair("BTC-USD")]).await.unwrap();
+        match client
+            .get_prices(&[pair("BTC-USD"), pair("FOO-BAR"), pair("SOL-USD")])
+            .await
+        {
+            Err(Error::InvalidArgument { message }) => {
+                assert_eq!(message, "unsupported trading pairs: FOO-BAR, SOL-USD");
+            }
+            r => panic!("Unexpected result: {r:?}"),
+        }
+        let end = Utc::now();
+        let mut prices = client
+            .stream_historical_prices(
+                &[pair("FOO-BAR")],
+                end - ChronoDuration::hours(1),
+                end,
+                Granularity::Minute,
+            )
+            .unwrap();
+        assert!(matches!(
+            prices.try_next().await,
+            Err(Error::InvalidArgument { .. })
+        ));
+
+        // The supported pairs are fetched once and cached.
+        let paths: Vec<_> = server
+            .http_requests()
+            .into_iter()
+            .map(|request| request.path)
+            .collect();
+        assert_eq!(paths, vec!["/v1/prices/pairs", "/v1/prices"]);
+
+        // Without caching they are fetched on every check.
+        let client = server.client().await.unwrap();
+        client.validate_pairs(&[pair("ETH-USD")]).await.unwrap();
+        assert!(client.validate_pairs(&[pair("FOO-BAR")]).await.is_err());
+        assert_eq!(server.http_requests().len(), 4);
+    }
+
+    async fn next_tick(ticks: &mut BoxStream<'static, spiceai::Result<PriceTick>>) -> PriceTick {
+        tokio::time::timeout(Duration::from_secs(5), ticks.try_next())
+            .await
+            .expect("no tick within 5s")
+            .unwrap()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_subscribe_prices() {
+        let server = start().await;
+        let latest = |btc: &str| {
+            MockResponse::json(&json!({
+                "BTC-USD": {"prices": {"coinbase": btc}},
+                "ETH-USD": {"prices": {"coinbase": "2500.0"}}
+            }))
+        };
+        server.mock_http("/v1/prices", latest("42000.5"));
+        let client = server
+            .builder()
+            .retry_policy(RetryPolicy::none())
+            .build()
+            .await
+            .unwrap();
+        let pairs = [pair("BTC-USD"), pair("ETH-USD")];
+
+        assert!(client
+            .subscribe_prices(&[], Duration::from_millis(10))
+            .is_err());
+        assert!(client.subscribe_prices(&pairs, Duration::ZERO).is_err());
+
+        let mut ticks = client
+            .subscribe_prices(&pairs, Duration::from_millis(10))
+            .unwrap();
+        let first = (next_tick(&mut ticks).await, next_tick(&mut ticks).await);
+        assert_eq!(first.0.pair, pair("BTC-USD"));
+        assert_eq!(first.0.detail.prices["coinbase"], 42000.5);
+        assert_eq!(first.1.pair, pair("ETH-USD"));
+
+        // Rate limited polls are backed off without yielding an error, and
+        // only the changed pair is yielded afterwards.
+        server.fail_next_http(MockResponse::new(StatusCode::TOO_MANY_REQUESTS));
+        server.fail_next_http(MockResponse::new(StatusCode::TOO_MANY_REQUESTS));
+        server.mock_http("/v1/prices", latest("42001.0"));
+        let tick = next_tick(&mut ticks).await;
+        assert_eq!(tick.pair, pair("BTC-USD"));
+        assert_eq!(tick.detail.prices["coinbase"], 42001.0);
+        assert!(tick.received_at >= first.1.received_at);
+        drop(ticks);
+
+        let polls = server.http_requests().len();
+        assert!(polls >= 4, "{polls}");
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(server.http_requests().len(), polls);
+    }
+
+    #[tokio::test]
+    async fn test_prices_record_batch() {
+        let server = start().await;
+        server.mock_http(
+            "/v1/prices",
+            MockResponse::json(&json!({
+                "ETH-USD": {"prices": {"coinbase": "2500.0"}, "meanPrice": "2500.0"},
+                "BTC-USD": {
+                    "prices": {"coinbase": "42000.0", "kraken": "42001.0"},
+                    "minPrice": "42000.0",
+                    "maxPrice": "42001.0",
+                    "meanPrice": "42000.5"
+                }
+            })),
+        );
+        server.mock_http(
+            "/v1/prices/historical",
+            MockResponse::json(&json!({
+                "ETH-USD": [{"timestamp": "2024-01-01T00:00:00Z", "price": 2500.0}],
+                "BTC-USD": [
+                    {"timestamp": "2024-01-01T00:00:00Z", "price": 41000.0, "high": 41500.0,
+                     "low": 40500.0, "open": 40800.0, "close": 41200.0},
+                    {"timestamp": "2024-01-01T01:00:00.250Z", "price": 41100.0}
+                ]
+            })),
+        );
+        let client = server.client().await.unwrap();
+        let pairs = [pair("BTC-USD"), pair("ETH-USD")];
+
+        let historical = client
+            .get_historical_prices(&pairs, None, None, None)
+            .await
+            .unwrap();
+        let batch = historical_prices_to_record_batch(&historical).unwrap();
+        assert_eq!(batch.schema(), prices_schema());
+        let mut csv = Vec::new();
+        assert_eq!(
+            write_batches(std::slice::from_ref(&batch), ExportFormat::Csv, &mut csv).unwrap(),
+            3
+        );
+        assert_eq!(
+            String::from_utf8(csv).unwrap(),
+            "pair,timestamp,price,open,high,low,close\n\
+             BTC-USD,2024-01-01T00:00:00Z,41000.0,40800.0,41500.0,40500.0,41200.0\n\
+             BTC-USD,2024-01-01T01:00:00.250Z,41100.0,,,,\n\
+             ETH-USD,2024-01-01T00:00:00Z,2500.0,,,,\n"
+        );
+        assert_eq!(
+            historical_prices_from_record_batch(&batch).unwrap(),
+            historical
+        );
+
+        let latest = client.get_prices(&pairs).await.unwrap();
+        let batch = latest.to_record_batch().unwrap();
+        assert_eq!(batch.num_rows(), 2);
+        assert_eq!(batch.column(1).null_count(), 2);
+        let read = LatestPricesResponse::from_record_batch(&batch).unwrap();
+        let btc = &read.prices[&pair("BTC-USD")];
+        assert_eq!(
+            (btc.min_price, btc.max_price, btc.mean_price),
+            (Some(42000.0), Some(42001.0), Some(42000.5))
+        );
+        assert!(btc.prices.is_empty());
+        // Latest prices have no timestamp, so they are not historical prices.
+        assert!(matches!(
+            historical_prices_from_record_batch(&batch),
+            Err(Error::InvalidArgument { .. })
+        ));
+    }
+}
@@ -0,0 +1,245 @@
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use arrow::array::{Array, Int32Array, Int64Array, StringArray};
+    use arrow::datatypes::{DataType, Field, Schema, SchemaRef};
+    use arrow::record_batch::RecordBatch;
+    use spiceai::testing::{Cassette, FlightCall, MockServer};
+    use spiceai::{Error, ParameterValue};
+
+    const BLOCKS: &str = "SELECT number, hash FROM eth.blocks WHERE number > $1 AND hash <> $2";
+
+    fn blocks() -> RecordBatch {
+        RecordBatch::try_from_iter([
+            ("number", Arc::new(Int64Array::from(vec![1, 2, 3])) as _),
+            (
+                "hash",
+                Arc::new(StringArray::from(vec!["0x1", "0x2", "0x3"])) as _,
+            ),
+        ])
+        .unwrap()
+    }
+
+    fn parameters() -> SchemaRef {
+        Arc::new(Schema::new(vec![
+            Field::new("number", DataType::Int32, true),
+            Field::new("hash", DataType::Utf8, true),
+        ]))
+    }
+
+    async fn start() -> MockServer {
+        let server = MockServer::start().await.expect("Failed to start mock");
+        server.add_query(BLOCKS, vec![blocks()]);
+        server.set_parameter_schema(BLOCKS, parameters());
+        server
+    }
+
+    /// Waits for the statements closed in the background to be released.
+    async fn wait_closed(server: &MockServer) {
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while server.open_prepared_statements() > 0 {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("Prepared statement was not closed");
+    }
+
+    #[tokio::test]
+    async fn test_prepare_and_execute() {
+        let server = start().await;
+        let client = server.client().await.unwrap();
+        let mut statement = client.prepare(BLOCKS).await.unwrap();
+        assert_eq!(statement.dataset_schema(), &blocks().schema());
+        assert_eq!(statement.parameter_schema(), &parameters());
+        assert_eq!(server.open_prepared_statements(), 1);
+
+        statement
+            .bind_values([ParameterValue::from(1), "0x2".into()])
+            .unwrap();
+        let result = statement.execute().await.unwrap();
+        assert_eq!(result.concat().await.unwrap(), blocks());
+
+        let bound = server.bound_parameters();
+        assert_eq!(bound.len(), 1);
+        assert_eq!(bound[0].schema(), parameters());
+        let number = bound[0].column(0).as_any().downcast_ref::<Int32Array>();
+        assert_eq!(number.unwrap().value(0), 1);
+        let hash = bound[0].column(1).as_any().downcast_ref::<StringArray>();
+        assert_eq!(hash.unwrap().value(0), "0x2");
+
+        // Statements can be executed again with new parameters.
+        statement
+            .bind_values([ParameterValue::from(2), "0x3".into()])
+            .unwrap();
+        statement
+            .execute()
+            .await
+            .unwrap()
+            .row_count()
+            .await
+            .unwrap();
+        assert_eq!(server.bound_parameters().len(), 2);
+
+        statement.close().await.unwrap();
+        assert_eq!(server.open_prepared_statements(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_execute_without_parameters() {
+        let server = MockServer::start().await.unwrap();
+        server.add_query("SELECT 1", vec![blocks()]);
+        let client = server.client().await.unwrap();
+        let statement = client.prepare("SELECT 1").await.unwrap();
+        assert!(statement.parameter_schema().fields().is_empty());
+        assert_eq!(
+            statement.execute().await.unwrap().concat().await.unwrap(),
+            blocks()
+        );
+        assert!(server
+            .flight_requests()
+            .iter()
+            .all(|request| request.call != FlightCall::DoPut));
+    }
+
+    #[tokio::test]
+    async fn test_prepare_unknown_query() {
+        let server = start().await;
+        let client = server.client().await.unwrap();
+        assert!(matches!(
+            client.prepare("SELECT 2").await,
+            Err(Error::Flight { .. })
+        ));
+        assert_eq!(server.open_prepared_statements(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_bind_values_casts_to_parameter_types() {
+        let server = start().await;
+        let client = server.client().await.unwrap();
+        let mut statement = client.prepare(BLOCKS).await.unwrap();
+
+        statement
+            .bind_values([ParameterValue::from(7_u8), 42_i64.into()])
+            .unwrap();
+        statement.execute().await.unwrap();
+        let bound = server.bound_parameters().pop().unwrap();
+        assert_eq!(bound.column(0).data_type(), &DataType::Int32);
+        let hash = bound.column(1).as_any().downcast_ref::<StringArray>();
+        assert_eq!(hash.unwrap().value(0), "42");
+
+        statement
+            .bind_values([ParameterValue::Null, None::<&str>.into()])
+            .unwrap();
+        statement.execute().await.unwrap();
+        let bound = server.bound_parameters().pop().unwrap();
+        assert_eq!(bound.schema(), parameters());
+        assert!(bound.column(0).is_null(0));
+        assert!(bound.column(1).is_null(0));
+    }
+
+    #[tokio::test]
+    async fn test_bind_values_without_parameter_schema() {
+        let server = MockServer::start().await.unwrap();
+        server.add_query("SELECT $1", vec![blocks()]);
+        let client = server.client().await.unwrap();
+        let mut statement = client.prepare("SELECT $1").await.unwrap();
+
+        statement
+            .bind_values([ParameterValue::from(1), "a".into()])
+            .unwrap();
+        statement.execute().await.unwrap();
+        let bound = server.bound_parameters().pop().unwrap();
+        let schema = bound.schema();
+        assert_eq!(schema.field(0).name(), "$1");
+        assert_eq!(schema.field(0).data_type(), &DataType::Int64);
+        assert_eq!(schema.field(1).name(), "$2");
+        assert_eq!(schema.field(1).data_type(), &DataType::Utf8);
+    }
+
+    #[tokio::test]
+    async fn test_bind_errors() {
+        let server = start().await;
+        let client = server.client().await.unwrap();
+        let mut statement = client.prepare(BLOCKS).await.unwrap();
+
+        assert!(matches!(
+            statement.bind_values([1]),
+            Err(Error::InvalidArgument { .. })
+        ));
+        match statement.bind_values([ParameterValue::from("one"), "0x1".into()]) {
+            Err(Error::InvalidArgument { message }) => {
+                assert!(message.contains("parameter number"), "{message}");
+            }
+            r => panic!("Unexpected result: {:?}", r.err()),
+        }
+        assert!(matches!(
+            statement.bind_values([ParameterValue::from(i64::MAX), "0x1".into()]),
+            Err(Error::InvalidArgument { .. })
+        ));
+        assert!(matches!(
+            statement.bind(blocks().project(&[0]).unwrap()),
+            Err(Error::InvalidArgument { .. })
+        ));
+
+        // Nothing was bound, so nothing is sent.
+        statement.execute().await.unwrap();
+        assert!(server.bound_parameters().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_drop_closes_statement() {
+        let server = start().await;
+        let client = server.client().await.unwrap();
+        let statement = client.prepare(BLOCKS).await.unwrap();
+        let other = client.prepare(BLOCKS).await.unwrap();
+        assert_eq!(server.open_prepared_statements(), 2);
+
+        other.close().await.unwrap();
+        assert_eq!(server.open_prepared_statements(), 1);
+        drop(statement);
+        wait_closed(&server).await;
+    }
+
+    #[tokio::test]
+    async fn test_replay_prepared_statement() {
+        let path =
+            std::env::temp_dir().join(format!("spiceai-prepared-{}.json", std::process::id()));
+        let server = start().await;
+        let cassette = Cassette::record(&path);
+        let client = server
+            .builder()
+            .cassette(cassette.clone())
+            .build()
+            .await
+            .unwrap();
+        let mut statement = client.prepare(BLOCKS).await.unwrap();
+        statement
+            .bind_values([ParameterValue::from(1), "0x2".into()])
+            .unwrap();
+        statement.execute().await.unwrap().concat().await.unwrap();
+        statement.close().await.unwrap();
+        cassette.save().unwrap();
+
+        let cassette = Cassette::replay(&path).unwrap();
+        let client = server
+            .builder()
+            .cassette(cassette.clone())
+            .build()
+            .await
+            .unwrap();
+        drop(server);
+        let mut statement = client.prepare(BLOCKS).await.unwrap();
+        assert_eq!(statement.parameter_schema(), &parameters());
+        statement
+            .bind_values([ParameterValue::from(1), "0x2".into()])
+            .unwrap();
+        let result = statement.execute().await.unwrap();
+        assert_eq!(result.concat().await.unwrap(), blocks());
+        statement.close().await.unwrap();
+        assert_eq!(cassette.unplayed(), 0);
+        std::fs::remove_file(path).unwrap();
+    }
+}
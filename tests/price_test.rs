@@ -1,7 +1,7 @@
 #[cfg(test)]
 mod tests {
     use chrono::{TimeZone, Utc};
-    use spiceai::Client;
+    use spiceai::{Client, Granularity, TradingPair};
     use std::env;
     use std::path::Path;
 
@@ -23,7 +23,7 @@ mod tests {
     #[tokio::test]
     async fn test_get_prices() {
         let spice_client = new_client().await;
-        let pair = "BTC-USD";
+        let pair: TradingPair = "BTC-USD".parse().unwrap();
         let result = spice_client.get_prices(&[pair]).await;
         assert!(result.is_ok());
     }
@@ -31,14 +31,19 @@ mod tests {
     #[tokio::test]
     async fn test_get_historical_prices() {
         let spice_client = new_client().await;
-        let pair1 = "BTC-USD";
-        let pair2 = "ETH-USD";
+        let pair1: TradingPair = "BTC-USD".parse().unwrap();
+        let pair2: TradingPair = "ETH-USD".parse().unwrap();
 
         let start_time = Utc.timestamp_opt(1697669766, 0).single();
         let end_time = Utc.timestamp_opt(1697756166, 0).single();
 
         let result = spice_client
-            .get_historical_prices(&[pair1, pair2], start_time, end_time, Some("1h"))
+            .get_historical_prices(
+                &[pair1, pair2],
+                start_time,
+                end_time,
+                Some(Granularity::Hour),
+            )
             .await;
         assert!(result.is_ok());
     }
@@ -12,7 +12,7 @@ mod tests {
         dotenv::from_path(Path::new(".env.local")).ok();
         let api_key = env::var("API_KEY").expect("API_KEY not found");
 
-        let mut client = Client::new(&api_key).await.unwrap();
+        let client = Client::new(&api_key).await.unwrap();
         let data = client
             .query("SELECT * FROM eth.recent_blocks LIMIT 10;")
             .await;
@@ -26,12 +26,13 @@ mod tests {
                 supported_pairs.expect_err("")
             )
         }
-        let price_data = client.get_prices(&["BTC-USDC"]).await;
+        let pairs: [TradingPair; 1] = ["BTC-USDC".parse().unwrap()];
+        let price_data = client.get_prices(&pairs).await;
         if price_data.is_err() {
             panic!("failed to get prices: {:#?}", price_data.expect_err(""))
         }
         let historical_price_data = client
-            .get_historical_prices(&["BTC-USDC"], Option::None, Option::None, Option::None)
+            .get_historical_prices(&pairs, Option::None, Option::None, Option::None)
             .await;
         if historical_price_data.is_err() {
             panic!(
@@ -43,7 +44,7 @@ mod tests {
         let start = now.sub(Duration::seconds(3600));
 
         let historical_price_data = client
-            .get_historical_prices(&["BTC-USDC"], Some(start), Some(now), Option::None)
+            .get_historical_prices(&pairs, Some(start), Some(now), Option::None)
             .await;
         if historical_price_data.is_err() {
             panic!(
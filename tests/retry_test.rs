@@ -0,0 +1,26 @@
+#[cfg(test)]
+mod tests {
+    use spiceai::{Error, RetryPolicy};
+    use std::time::Duration;
+
+    #[test]
+    fn test_default_retryable_errors() {
+        let policy = RetryPolicy::default();
+        assert!(policy.is_retryable(&tonic::Status::unavailable("down").into()));
+        assert!(policy.is_retryable(&tonic::Status::resource_exhausted("busy").into()));
+        assert!(policy.is_retryable(&Error::RateLimited {
+            retry_after: Some(Duration::from_secs(1))
+        }));
+        assert!(!policy.is_retryable(&tonic::Status::invalid_argument("bad sql").into()));
+        assert!(!policy.is_retryable(&Error::BadRequest {
+            message: "bad pair".to_string()
+        }));
+    }
+
+    #[test]
+    fn test_custom_retryable_codes() {
+        let policy = RetryPolicy::default().with_retryable_codes([tonic::Code::Internal]);
+        assert!(policy.is_retryable(&tonic::Status::internal("oops").into()));
+        assert!(!policy.is_retryable(&tonic::Status::unavailable("down").into()));
+    }
+}
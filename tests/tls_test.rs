@@ -0,0 +1,144 @@
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use arrow::array::Int64Array;
+    use arrow::record_batch::RecordBatch;
+    use spiceai::testing::MockServer;
+    use spiceai::{Error, TlsRoots};
+    use tonic::transport::{Certificate, Identity, ServerTlsConfig};
+
+    const QUERY: &str = "SELECT number FROM eth.blocks";
+    const CA: &[u8] = include_bytes!("data/tls/ca.pem");
+    const SERVER_CERT: &[u8] = include_bytes!("data/tls/server.pem");
+    const SERVER_KEY: &[u8] = include_bytes!("data/tls/server.key");
+    const CLIENT_CERT: &[u8] = include_bytes!("data/tls/client.pem");
+    const CLIENT_KEY: &[u8] = include_bytes!("data/tls/client.key");
+
+    /// Starts a mock whose Flight server requires a client certificate issued
+    /// by the test CA.
+    async fn start() -> MockServer {
+        let tls = ServerTlsConfig::new()
+            .identity(Identity::from_pem(SERVER_CERT, SERVER_KEY))
+            .client_ca_root(Certificate::from_pem(CA));
+        let server = MockServer::start_tls(tls).await.unwrap();
+        let batch =
+            RecordBatch::try_from_iter([("number", Arc::new(Int64Array::from(vec![1, 2])) as _)])
+                .unwrap();
+        server.add_query(QUERY, vec![batch]);
+        server
+    }
+
+    fn builder(server: &MockServer) -> spiceai::SpiceClientBuilder {
+        server.builder().connect_timeout(Duration::from_secs(5))
+    }
+
+    #[tokio::test]
+    async fn test_mutual_tls() {
+        let server = start().await;
+        let client = builder(&server)
+            .ca_certificate(CA)
+            .client_identity(CLIENT_CERT, CLIENT_KEY)
+            .build()
+            .await
+            .unwrap();
+        let rows = client
+            .query(QUERY)
+            .await
+            .unwrap()
+            .row_count()
+            .await
+            .unwrap();
+        assert_eq!(rows, 2);
+    }
+
+    #[tokio::test]
+    async fn test_untrusted_server() {
+        let server = start().await;
+        for roots in [TlsRoots::Native, TlsRoots::WebPki] {
+            let result = builder(&server)
+                .tls_roots(roots)
+                .client_identity(CLIENT_CERT, CLIENT_KEY)
+                .build()
+                .await;
+            assert!(matches!(result, Err(Error::Transport(_))), "{roots:?}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_missing_client_certificate() {
+        let server = start().await;
+        let result = match builder(&server).ca_certificate(CA).build().await {
+            Ok(client) => client.query(QUERY).await.map(|_| ()),
+            Err(e) => Err(e),
+        };
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_domain_name_override() {
+        let server = start().await;
+        // The server certificate names `localhost` and `spice.test`, not the IP.
+        let url = server.flight_url().replace("localhost", "127.0.0.1");
+        let client = |domain_name: Option<&str>| {
+            let mut builder = builder(&server)
+                .flight_url(url.clone())
+                .firecache_url(url.clone())
+                .ca_certificate(CA)
+                .client_identity(CLIENT_CERT, CLIENT_KEY);
+            if let Some(domain_name) = domain_name {
+                builder = builder.tls_domain_name(domain_name);
+            }
+            builder.build()
+        };
+
+        assert!(client(None).await.is_err());
+        let client = client(Some("spice.test")).await.unwrap();
+        let rows = client
+            .query(QUERY)
+            .await
+            .unwrap()
+            .row_count()
+            .await
+            .unwrap();
+        assert_eq!(rows, 2);
+    }
+
+    #[tokio::test]
+    async fn test_tls_endpoint_location() {
+        let server = start().await;
+        let port = server.flight_url().rsplit(':').next().unwrap().to_string();
+        server.set_endpoint_location(format!("grpc+tls://localhost:{port}"));
+        let url = server.flight_url().replace("localhost", "127.0.0.1");
+        let client = builder(&server)
+            .flight_url(url.clone())
+            .firecache_url(url)
+            .tls_domain_name("spice.test")
+            .ca_certificate(CA)
+            .client_identity(CLIENT_CERT, CLIENT_KEY)
+            .build()
+            .await
+            .unwrap();
+        let rows = client
+            .query(QUERY)
+            .await
+            .unwrap()
+            .row_count()
+            .await
+            .unwrap();
+        assert_eq!(rows, 2);
+    }
+
+    #[tokio::test]
+    async fn test_invalid_ca_certificate() {
+        let result = MockServer::start()
+            .await
+            .unwrap()
+            .builder()
+            .ca_certificate("not a certificate")
+            .build()
+            .await;
+        assert!(matches!(result, Err(Error::Config { .. })));
+    }
+}